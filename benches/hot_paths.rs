@@ -0,0 +1,115 @@
+//! Benchmarks for course generation and scoring, the work done for every player on every
+//! landing. Run with `cargo bench`, `JUMP_*` settings in the environment are picked up the same
+//! way the server does.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use parkourqueue::{
+    CourseBlocks, JumpProfile, MIN_ADAPTIVE_HARDNESS, START_POS, generate_random_block,
+    is_plausible_landing, update_combo,
+};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use valence::prelude::BlockPos;
+
+/// A course of `count` blocks after the start block, with the start block first.
+fn course(profile: &JumpProfile, count: usize) -> Vec<BlockPos> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut course = vec![START_POS];
+    for _ in 0..count {
+        let last = *course.last().unwrap();
+        course.push(generate_random_block(
+            last,
+            0,
+            profile,
+            f64::INFINITY,
+            &mut rng,
+        ));
+    }
+    course
+}
+
+fn generation(c: &mut Criterion) {
+    let profile = JumpProfile::from_env().expect("Invalid jump profile");
+
+    c.bench_function("generate_random_block", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        b.iter(|| {
+            generate_random_block(black_box(START_POS), 0, &profile, f64::INFINITY, &mut rng)
+        });
+    });
+
+    // Adaptive courses reroll a lot more for slow players
+    c.bench_function("generate_random_block/min_hardness", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        b.iter(|| {
+            generate_random_block(
+                black_box(START_POS),
+                0,
+                &profile,
+                MIN_ADAPTIVE_HARDNESS,
+                &mut rng,
+            )
+        });
+    });
+
+    c.bench_function("generate_course/1000", |b| {
+        b.iter(|| course(&profile, black_box(1000)));
+    });
+}
+
+fn landing(c: &mut Criterion) {
+    let profile = JumpProfile::from_env().expect("Invalid jump profile");
+
+    // Finding the block a player landed on, with the course's place map and with the linear
+    // scan it replaced, for a regular course and ones grown past `COURSE_LENGTH`
+    let mut group = c.benchmark_group("landing_lookup");
+    for length in [11, 100, 1000] {
+        let mut blocks = CourseBlocks::default();
+        for block in course(&profile, length - 1) {
+            blocks.push_back(block);
+        }
+        let last = *blocks.back().unwrap();
+
+        group.bench_with_input(BenchmarkId::new("place_map", length), &last, |b, &last| {
+            b.iter(|| blocks.position(black_box(last)));
+        });
+        group.bench_with_input(
+            BenchmarkId::new("linear_scan", length),
+            &last,
+            |b, &last| {
+                b.iter(|| blocks.iter().position(|&block| block == black_box(last)));
+            },
+        );
+    }
+    group.finish();
+
+    let course = course(&profile, 1000);
+    c.bench_function("is_plausible_landing", |b| {
+        b.iter(|| {
+            course
+                .windows(2)
+                .filter(|jump| is_plausible_landing(jump[0], jump[1], black_box(500)))
+                .count()
+        });
+    });
+}
+
+fn scoring(c: &mut Criterion) {
+    // A run of landings that mostly keeps its combo going, breaking it every 50th landing
+    let landings: Vec<(u32, u128)> = (0..1000)
+        .map(|i| (1 + i % 3, if i % 50 == 0 { 2000 } else { 400 }))
+        .collect();
+
+    c.bench_function("update_combo/1000_landings", |b| {
+        b.iter(|| {
+            landings.iter().fold(0, |combo, &(blocks, elapsed_ms)| {
+                update_combo(combo, blocks, black_box(elapsed_ms), 100, 1000)
+            })
+        });
+    });
+}
+
+criterion_group!(benches, generation, landing, scoring);
+criterion_main!(benches);
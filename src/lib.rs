@@ -0,0 +1,416 @@
+//! Course generation, jump physics and combo scoring, along with reading the settings they're
+//! tuned with. Split out of the server so the benches in `benches/` can drive them.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::mem;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+use rand::Rng;
+use rand::prelude::IndexedRandom;
+use rand::rngs::StdRng;
+use valence::prelude::*;
+
+pub const START_POS: BlockPos = BlockPos::new(0, 100, 0);
+// Build height of the_end, courses have to stay below it
+pub const WORLD_HEIGHT: i32 = 256;
+
+// Jump physics shared by generation and landing checks. Gaps are measured between the edges
+// of two blocks, a sprint jump clears 4 blocks landing level or lower but only 3 going up.
+pub const MAX_JUMP_UP: i32 = 1;
+pub const MAX_GAP_LEVEL: f64 = 4.0;
+pub const MAX_GAP_UP: f64 = 3.0;
+pub const MAX_PLAYER_SPEED: f64 = 10.0; // blocks per second
+
+// Hardest jump adaptive generation eases off to, as a fraction of the longest reachable gap.
+// Every kind of jump needs a landing at least this easy, see `jump_z_range`.
+pub const MIN_ADAPTIVE_HARDNESS: f64 = 0.6;
+
+/// Settings from `CONFIG_FILE`, which take precedence over the environment. Unlike the
+/// environment they can be changed while the server is running, with `/reloadconfig`.
+pub static CONFIG_FILE_VARS: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// A setting from `CONFIG_FILE` if it's set there, otherwise from the environment.
+pub fn config_var(key: &str) -> Result<String, std::env::VarError> {
+    let file_vars = CONFIG_FILE_VARS.lock().unwrap();
+    match file_vars.as_ref().and_then(|vars| vars.get(key)) {
+        Some(value) => Ok(value.clone()),
+        None => std::env::var(key),
+    }
+}
+
+/// Parses a setting read with `config_var`, `None` when it's unset.
+pub fn parse_var<T: std::str::FromStr>(key: &str) -> Result<Option<T>, String> {
+    config_var(key)
+        .ok()
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| format!("Failed to parse {}", key))
+        })
+        .transpose()
+}
+
+/// Parses a comma separated list read with `config_var`, skipping empty entries. `None` when
+/// it's unset.
+pub fn parse_list_var<T: std::str::FromStr>(key: &str) -> Result<Option<Vec<T>>, String> {
+    config_var(key)
+        .ok()
+        .map(|list| {
+            list.split(',')
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .map(|item| item.parse().map_err(|_| format!("Failed to parse {}", key)))
+                .collect()
+        })
+        .transpose()
+}
+
+/// Reads `CONFIG_FILE` as `KEY=VALUE` lines, skipping blank lines and `#` comments. Nothing
+/// changes if the file can't be read.
+pub fn load_config_file() -> Result<(), String> {
+    let Ok(path) = std::env::var("CONFIG_FILE") else {
+        return Ok(());
+    };
+    let contents =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    let mut vars = HashMap::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Expected KEY=VALUE in {}, got {:?}", path, line))?;
+        vars.insert(key.trim().to_string(), value.trim().to_string());
+    }
+
+    *CONFIG_FILE_VARS.lock().unwrap() = Some(vars);
+    Ok(())
+}
+
+/// Whether HARDCORE is set. The server's config and the jump profile both check it.
+pub fn hardcore() -> bool {
+    config_var("HARDCORE").is_ok_and(|hardcore| hardcore == "1")
+}
+
+/// Reads the forward distances of jumps `dy` blocks up or down as `min-max`. Even the longest
+/// has to be reachable straight ahead, and the shortest easy enough for adaptive courses, or
+/// generation could never find a block.
+pub fn jump_z_range(var: &str, dy: i32, default: (i32, i32)) -> Result<(i32, i32), String> {
+    let (min, max) = match config_var(var) {
+        Ok(range) => {
+            let (min, max) = range
+                .split_once('-')
+                .ok_or_else(|| format!("{} must be formatted as min-max", var))?;
+            let parse = |z: &str| -> Result<i32, String> {
+                z.trim()
+                    .parse()
+                    .map_err(|_| format!("Failed to parse {}", var))
+            };
+            (parse(min)?, parse(max)?)
+        }
+        Err(_) => default,
+    };
+
+    let landing = |z| BlockPos::new(START_POS.x, START_POS.y + dy, START_POS.z + z);
+    let valid = 1 <= min
+        && min <= max
+        && is_reachable(START_POS, landing(max))
+        && jump_hardness(START_POS, landing(min)) <= MIN_ADAPTIVE_HARDNESS;
+    if !valid {
+        return Err(format!(
+            "{} must be a reachable range of at least 1 block",
+            var
+        ));
+    }
+    Ok((min, max))
+}
+
+/// Tuning for how parkour courses are generated.
+#[derive(Clone, Debug, Resource)]
+pub struct JumpProfile {
+    /// Chance between 0.0 and 1.0 that a jump stays level rather than going up or down. High
+    /// flow gives mostly flat, long jumps; low flow gives technical courses with height changes.
+    /// `None` keeps the original even spread, so existing champion seeds regenerate the same
+    /// course.
+    pub flow: Option<f64>,
+    /// Scores at which a run enters the next difficulty tier, in ascending order.
+    pub tiers: Vec<u32>,
+    /// How far a course may wander above or below the start before it's steered back towards
+    /// it. Small bands give flat courses, large ones very vertical courses.
+    pub y_band: i32,
+    /// Eases off the hardest jumps for players who are still slow. Courses then depend on who's
+    /// playing them, so this is off by default.
+    pub adaptive: bool,
+    /// How far forward jumps going up, staying level and going down land, as inclusive ranges
+    /// of blocks. Falling carries a jump further, so descents can be longer than the rest.
+    pub z_up: (i32, i32),
+    pub z_level: (i32, i32),
+    pub z_down: (i32, i32),
+    /// Least air, in blocks, between a block and the next, so no jump can be walked. Any
+    /// minimum changes the course every seed generates, so it's 0 by default; 1 rules out
+    /// stepping straight or diagonally onto a neighbouring block.
+    pub min_gap: f64,
+}
+
+impl JumpProfile {
+    pub fn from_env() -> Result<Self, String> {
+        let flow: Option<f64> = parse_var("JUMP_FLOW")?;
+        if flow.is_some_and(|flow| !(0.0..=1.0).contains(&flow)) {
+            return Err("JUMP_FLOW must be between 0.0 and 1.0".to_string());
+        }
+
+        let mut tiers: Vec<u32> = parse_list_var("JUMP_TIERS")?.unwrap_or_default();
+        tiers.sort_unstable();
+        tiers.dedup();
+
+        // Courses overshoot the band by up to two blocks before turning around, which has to stay
+        // inside the world. Turning around is a regular one block step, so any band is reachable.
+        let y_band: i32 = parse_var("JUMP_Y_BAND")?.unwrap_or(30);
+        let inside_world =
+            y_band >= 0 && y_band + 2 <= START_POS.y && START_POS.y + y_band + 2 < WORLD_HEIGHT;
+        if !inside_world {
+            return Err("JUMP_Y_BAND must keep courses inside the world".to_string());
+        }
+
+        let adaptive =
+            !hardcore() && config_var("JUMP_ADAPTIVE").is_ok_and(|adaptive| adaptive == "1");
+
+        let min_gap: f64 = parse_var("JUMP_MIN_GAP")?.unwrap_or(0.0);
+        if min_gap < 0.0 {
+            return Err("JUMP_MIN_GAP can't be negative".to_string());
+        }
+
+        let profile = JumpProfile {
+            flow,
+            tiers,
+            y_band,
+            adaptive,
+            z_up: jump_z_range("JUMP_Z_UP", 1, (1, 2))?,
+            z_level: jump_z_range("JUMP_Z_LEVEL", 0, (1, 3))?,
+            z_down: jump_z_range("JUMP_Z_DOWN", -1, (2, 4))?,
+            min_gap,
+        };
+
+        // `generate_random_block` rerolls until a jump fits, so every kind of jump needs one that's
+        // long enough while still below the minimum adaptive hardness
+        for dy in -1..=1 {
+            let (min_z, max_z) = profile.z_range(dy);
+            let fits = (min_z..=max_z).any(|z| {
+                (-3..=3).any(|x| {
+                    let landing = BlockPos::new(START_POS.x + x, START_POS.y + dy, START_POS.z + z);
+                    is_reachable(START_POS, landing)
+                        && jump_gap(START_POS, landing) >= min_gap
+                        && jump_hardness(START_POS, landing) <= MIN_ADAPTIVE_HARDNESS
+                })
+            });
+            if !fits {
+                return Err("JUMP_MIN_GAP is too long for the jump ranges".to_string());
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Range of forward distances for a jump `dy` blocks up or down.
+    pub fn z_range(&self, dy: i32) -> (i32, i32) {
+        match dy {
+            1 => self.z_up,
+            -1 => self.z_down,
+            _ => self.z_level,
+        }
+    }
+
+    /// Blocks left until `score` reaches the next tier boundary, if there is one.
+    pub fn blocks_until_next_tier(&self, score: u32) -> Option<u32> {
+        self.tiers
+            .iter()
+            .find(|&&tier| tier > score)
+            .map(|tier| tier - score)
+    }
+}
+
+pub fn generate_random_block(
+    pos: BlockPos,
+    target_y: i32,
+    profile: &JumpProfile,
+    max_hardness: f64,
+    rng: &mut StdRng,
+) -> BlockPos {
+    // Reroll anything a player couldn't make. The default ranges are always reachable, so this
+    // only kicks in for tuned profiles and never changes courses generated from existing seeds.
+    // `jump_z_range` makes sure every kind of jump has a candidate below the minimum adaptive
+    // hardness, so this ends.
+    loop {
+        let y = match (target_y, profile.flow) {
+            (0, None) => rng.random_range(-1..2),
+            (0, Some(flow)) if rng.random_bool(flow) => 0,
+            (0, Some(_)) => *[-1, 1].choose(rng).unwrap(),
+            (y, _) if y > pos.y => 1,
+            _ => -1,
+        };
+        let (min_z, max_z) = profile.z_range(y);
+        let z = rng.random_range(min_z..max_z + 1);
+        let x = rng.random_range(-3..4);
+
+        let block_pos = BlockPos::new(pos.x + x, pos.y + y, pos.z + z);
+        if is_reachable(pos, block_pos)
+            && jump_gap(pos, block_pos) >= profile.min_gap
+            && jump_hardness(pos, block_pos) <= max_hardness
+            // Special blocks sit at or behind the start row, courses only move forward from it
+            && block_pos.z > START_POS.z
+        {
+            return block_pos;
+        }
+    }
+}
+
+/// Whether a player standing on `from` could have landed on `to` after `elapsed_ms`.
+pub fn is_plausible_landing(from: BlockPos, to: BlockPos, elapsed_ms: u128) -> bool {
+    let dx = f64::from(to.x - from.x);
+    let dz = f64::from(to.z - from.z);
+    let horizontal = (dx * dx + dz * dz).sqrt();
+    let min_time_ms = horizontal / MAX_PLAYER_SPEED * 1000.0;
+
+    is_reachable(from, to) && elapsed_ms as f64 >= min_time_ms
+}
+
+/// Whether a player standing on `from` can sprint jump onto `to`.
+pub fn is_reachable(from: BlockPos, to: BlockPos) -> bool {
+    let dy = to.y - from.y;
+    if dy > MAX_JUMP_UP {
+        return false;
+    }
+
+    let max_gap = if dy == MAX_JUMP_UP {
+        MAX_GAP_UP
+    } else {
+        MAX_GAP_LEVEL
+    };
+
+    jump_gap(from, to) <= max_gap
+}
+
+/// Air between two blocks horizontally, 0.0 for blocks next to each other, even diagonally.
+pub fn jump_gap(from: BlockPos, to: BlockPos) -> f64 {
+    let gap_x = f64::from((to.x - from.x).abs() - 1).max(0.0);
+    let gap_z = f64::from((to.z - from.z).abs() - 1).max(0.0);
+
+    gap_x.hypot(gap_z)
+}
+
+/// How hard a jump is, from 0.0 for stepping across to 1.0 for the longest reachable gap.
+pub fn jump_hardness(from: BlockPos, to: BlockPos) -> f64 {
+    let max_gap = if to.y - from.y == MAX_JUMP_UP {
+        MAX_GAP_UP
+    } else {
+        MAX_GAP_LEVEL
+    };
+
+    jump_gap(from, to) / max_gap
+}
+
+/// The combo after a landing `blocks` blocks ahead, `elapsed_ms` after the previous one. It grows
+/// by `blocks` if the landing took less than `combo_window_ms` per block, and breaks otherwise.
+/// The window is never below `floor_ms`, which is above 0, so an instant landing always keeps
+/// the combo. Callers take `elapsed_ms` with `saturating_sub`, so a clock going backwards counts
+/// as instant rather than underflowing.
+pub fn update_combo(
+    combo: u32,
+    blocks: u32,
+    elapsed_ms: u128,
+    floor_ms: u128,
+    ceiling_ms: u128,
+) -> u32 {
+    let max_time_taken = combo_window_ms(combo, floor_ms, ceiling_ms) * u128::from(blocks);
+
+    if elapsed_ms < max_time_taken {
+        combo.saturating_add(blocks)
+    } else {
+        0
+    }
+}
+
+/// Time per block a landing may take to keep a combo of `combo` going. Starts at `ceiling_ms`
+/// and halves every 45 combo, but never drops below `floor_ms`, where it would otherwise round
+/// down to nothing for huge combos.
+pub fn combo_window_ms(combo: u32, floor_ms: u128, ceiling_ms: u128) -> u128 {
+    let power_result = 2_f64.powf(f64::from(combo) / 45.0);
+    ((ceiling_ms as f64 / power_result) as u128).clamp(floor_ms, ceiling_ms)
+}
+
+/// The course blocks in the world, from the one the player stands on. Next to them is the place
+/// of each block in the course, so finding the block a player landed on doesn't scan the course
+/// every tick. Only changed through the methods here to keep both in sync.
+#[derive(Default)]
+pub struct CourseBlocks {
+    blocks: VecDeque<BlockPos>,
+    /// Places counting from the first block ever added, see `first`.
+    places: HashMap<BlockPos, u64>,
+    /// Place of `blocks[0]`.
+    first: u64,
+}
+
+impl CourseBlocks {
+    pub fn push_back(&mut self, block: BlockPos) {
+        self.places
+            .insert(block, self.first + self.blocks.len() as u64);
+        self.blocks.push_back(block);
+    }
+
+    pub fn pop_front(&mut self) -> Option<BlockPos> {
+        let block = self.blocks.pop_front()?;
+        if self.places.get(&block) == Some(&self.first) {
+            self.places.remove(&block);
+        }
+        self.first += 1;
+        Some(block)
+    }
+
+    /// Removes the first `count` blocks.
+    pub fn drain_front(&mut self, count: usize) -> Vec<BlockPos> {
+        (0..count).map_while(|_| self.pop_front()).collect()
+    }
+
+    /// Removes every block from `at` on.
+    pub fn split_off(&mut self, at: usize) -> Vec<BlockPos> {
+        let removed: Vec<BlockPos> = self.blocks.split_off(at).into();
+        for block in &removed {
+            self.places.remove(block);
+        }
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.places.clear();
+    }
+
+    /// Moves every block with `f`, keeping their order.
+    pub fn move_all(&mut self, f: impl Fn(BlockPos) -> BlockPos) {
+        let blocks = mem::take(&mut self.blocks);
+        self.places.clear();
+        for block in blocks {
+            self.push_back(f(block));
+        }
+    }
+
+    /// Index of `block` in the course blocks, if it's one of them.
+    pub fn position(&self, block: BlockPos) -> Option<usize> {
+        self.places
+            .get(&block)
+            .map(|place| (place - self.first) as usize)
+    }
+}
+
+impl Deref for CourseBlocks {
+    type Target = VecDeque<BlockPos>;
+
+    pub fn deref(&self) -> &Self::Target {
+        &self.blocks
+    }
+}
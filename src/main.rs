@@ -1,21 +1,35 @@
-use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
+use std::mem;
 use std::net::SocketAddr;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use bevy_ecs::removal_detection::RemovedComponents;
 use mimalloc::MiMalloc;
+use parkourqueue::{
+    CONFIG_FILE_VARS, CourseBlocks, JumpProfile, MIN_ADAPTIVE_HARDNESS, START_POS, config_var,
+    generate_random_block, hardcore, is_plausible_landing, load_config_file, parse_list_var,
+    parse_var, update_combo,
+};
 use rand::prelude::IndexedRandom;
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use valence::client::Properties;
+use valence::command::manager::CommandExecutionEvent;
 use valence::entity::HeadYaw;
-use valence::entity::entity::Flags;
+use valence::entity::Pose as EntityPose;
+use valence::entity::entity::{Flags, Pose};
 use valence::entity::player::PlayerEntityBundle;
-use valence::player_list::{DisplayName, Listed, PlayerListEntryBundle};
+use valence::network::{
+    HandshakeData, NetworkCallbacks, PlayerSampleEntry, ServerListPing, SharedNetworkState,
+    async_trait,
+};
+use valence::player_list::{DisplayName, Listed, PlayerList, PlayerListEntryBundle};
 use valence::prelude::*;
 use valence::client::despawn_disconnected_clients;
 use valence::protocol::WritePacket;
@@ -24,21 +38,122 @@ use valence::protocol::packets::play::{
     team_s2c::{CollisionRule, Mode, NameTagVisibility, TeamColor, TeamFlags},
 };
 use valence::protocol::sound::{Sound, SoundCategory};
+use valence::protocol::{MINECRAFT_VERSION, PROTOCOL_VERSION};
 use valence::scoreboard::*;
 use valence::spawn::IsFlat;
 use valence::title::SetTitle;
 use valence::{CompressionThreshold, ServerSettings};
 
-const START_POS: BlockPos = BlockPos::new(0, 100, 0);
+const GAME_DATA_FILE: &str = "gamedata.dat";
+// The best run beating the champion while it's still going, see `snapshot_pending_runs`
+const PENDING_RUN_FILE: &str = "pending_run.dat";
+
+// Falling below this ends a run
+const KILL_Y: i32 = START_POS.y - 32;
+// How often the kill plane's particles are sent, they fade out in about a second
+const KILL_PLANE_INTERVAL_MS: u128 = 1000;
 const VIEW_DIST: u8 = 10;
+// Half the width of a player's hitbox, a hair less so touching a block's side isn't standing on it
+const LANDING_HALF_WIDTH: f64 = 0.299;
 
 const BLOCK_TYPES: [BlockState; 1] = [BlockState::OBSIDIAN];
+// Colors a run's blocks can be with BLOCK_THEMES. Nothing black, yellow or light blue, so the
+// course never looks like the start, gold or diamond block
+const BLOCK_THEMES: [BlockState; 8] = [
+    BlockState::RED_CONCRETE,
+    BlockState::ORANGE_CONCRETE,
+    BlockState::LIME_CONCRETE,
+    BlockState::CYAN_CONCRETE,
+    BlockState::BLUE_CONCRETE,
+    BlockState::PURPLE_CONCRETE,
+    BlockState::MAGENTA_CONCRETE,
+    BlockState::PINK_CONCRETE,
+];
+
+// Course blocks placed ahead of the player unless COURSE_LENGTH says otherwise, and in blind mode
+const BLOCKS_AHEAD: usize = 10;
+const BLIND_BLOCKS_AHEAD: usize = 1;
+// Highest COMBO_BONUS, far more than any board would want, just keeping scores from overflowing
+const MAX_COMBO_BONUS: f64 = 100.0;
+// Only count down to the next difficulty tier once it's this close
+const TIER_COUNTDOWN_BLOCKS: u32 = 10;
+// How far ahead of the start a recentered course ends up at least, clear of the special blocks
+const RECENTER_MARGIN: i32 = 64;
+// How often the gap to the ghost in a race is sent again while it's unchanged
+const RACE_GAP_REFRESH_MS: u128 = 1000;
+// Blocks `/preview` shows by default, and at most
+const PREVIEW_BLOCKS: usize = 20;
+const MAX_PREVIEW_BLOCKS: usize = 100;
+// Color of the score in the action bar from each combo on, in ascending order of combo
+const COMBO_COLORS: [(u32, Color); 4] = [
+    (0, Color::WHITE),
+    (10, Color::YELLOW),
+    (25, Color::GOLD),
+    (50, Color::RED),
+];
+
+// Horizontal blocks a player can move in one tick before it counts as a teleport
+const MAX_TICK_MOVEMENT: f64 = 4.0;
+
+// Adaptive generation judges skill by the average time per block over the last few jumps. At or
+// above the slow time only jumps up to the minimum hardness are generated, at or below the fast
+// time anything reachable is.
+const SKILL_WINDOW: usize = 10;
+const SKILL_SLOW_JUMP_MS: f64 = 900.0;
+const SKILL_FAST_JUMP_MS: f64 = 500.0;
+
+// How far a ghost travels between footstep sounds
+const GHOST_STEP_SOUND_DISTANCE: f64 = 3.0;
+
+// First-join tutorial, each message is shown once the run reaches its score
+const TUTORIAL_STEPS: [(u32, &str); 4] = [
+    (
+        0,
+        "Jump onto the next block in front of you to start your run.",
+    ),
+    (
+        1,
+        "Every block you reach scores a point. Keep jumping quickly to build a combo!",
+    ),
+    (
+        5,
+        "Falling ends your run, and your best score goes on the sidebar.",
+    ),
+    (
+        10,
+        "You've got it! Switch boards with /board, and try the gold block once you're ready.",
+    ),
+];
+
+// Frames in the first moments of a run are left out of the replay so the initial settle isn't
+// recorded. Timestamps stay relative to the run start, so the ghost holds its first recorded
+// position for this long and then stays in sync with the original run.
+const RECORDING_GRACE_MS: u128 = 250;
+// How far AFK players can drift, in blocks, before they count as back
+const AFK_MOVE_THRESHOLD: f64 = 0.5;
+
+// Sparse recordings skip frames that moved less than this, in blocks and degrees
+const FRAME_POSITION_EPSILON: f64 = 0.001;
+const FRAME_ROTATION_EPSILON: f32 = 0.1;
+
+// Runs are counted by final score in buckets this many points wide, with the last bucket
+// holding every score past it
+const SCORE_HISTOGRAM_BUCKET: u32 = 10;
+const SCORE_HISTOGRAM_BUCKETS: usize = 50;
+
+// Top scores shown in the tab list footer, refreshed at most this often
+const TAB_LIST_TOP: usize = 5;
+const TAB_LIST_UPDATE_MS: u128 = 1000;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
 pub fn main() {
-    let connection_mode = match std::env::var("VELOCITY_SECRET") {
+    if let Err(e) = load_config_file() {
+        panic!("{}", e);
+    }
+
+    let connection_mode = match config_var("VELOCITY_SECRET") {
         Ok(velocity_secret) => {
             let secret_arc = Arc::from(velocity_secret);
             ConnectionMode::Velocity { secret: secret_arc }
@@ -46,952 +161,5881 @@ pub fn main() {
         Err(_) => ConnectionMode::Offline,
     };
 
-    let address = std::env::var("ADDRESS").unwrap_or_else(|_| "0.0.0.0:25565".to_string());
+    let address = config_var("ADDRESS").unwrap_or_else(|_| "0.0.0.0:25565".to_string());
     let address: SocketAddr = address.parse().expect("Failed to parse ADDRESS");
 
+    let data_dir = PathBuf::from(config_var("DATA_DIR").unwrap_or_else(|_| ".".to_string()));
+    if let Err(e) = prepare_data_dir(&data_dir) {
+        panic!("DATA_DIR {} is not writable: {}", data_dir.display(), e);
+    }
+    DATA_DIR.set(data_dir).unwrap();
+
+    let config = Config::from_env().unwrap_or_else(|e| panic!("{}", e));
+    let jump_profile = JumpProfile::from_env().unwrap_or_else(|e| panic!("{}", e));
+    let teams = Teams::from_env();
+    let permissions = Permissions::from_env().unwrap_or_else(|e| panic!("{}", e));
+    let special_blocks = SpecialBlocks::from_env();
+
+    let champion_status = ChampionStatus::default();
+    let jump_counter = JumpCounter::default();
+    let callbacks = Callbacks {
+        motd: config.motd.clone(),
+        favicon: config
+            .favicon
+            .as_ref()
+            .map(|path| fs::read(path).expect("Failed to read FAVICON"))
+            .unwrap_or_default(),
+        champion: champion_status.0.clone(),
+        total_jumps: jump_counter.0.clone(),
+    };
+
+    // Packets at least this many bytes long are compressed, -1 turns compression off. Course
+    // updates are small, so it's mostly chunks and the tab list that get compressed
+    let compression_threshold: i32 = config_var("COMPRESSION_THRESHOLD")
+        .map(|threshold| {
+            threshold
+                .parse()
+                .expect("Failed to parse COMPRESSION_THRESHOLD")
+        })
+        .unwrap_or(256);
+    assert!(
+        compression_threshold >= -1,
+        "COMPRESSION_THRESHOLD must be -1 or more"
+    );
+
     App::new()
         .insert_resource(ServerSettings {
-            compression_threshold: CompressionThreshold(-1),
+            compression_threshold: CompressionThreshold(compression_threshold),
             ..Default::default()
         })
         .insert_resource(NetworkSettings {
             connection_mode,
             max_players: i32::MAX as usize,
             address,
+            callbacks: callbacks.into(),
             ..Default::default()
         })
+        .insert_resource(config)
+        .insert_resource(champion_status)
+        .insert_resource(jump_counter)
+        .insert_resource(jump_profile)
+        .insert_resource(teams)
+        .insert_resource(permissions)
+        .insert_resource(special_blocks)
+        .init_resource::<Paused>()
+        .init_resource::<LastRuns>()
         .add_plugins(DefaultPlugins)
         .add_systems(Startup, setup)
         .add_systems(
             Update,
             (
                 init_clients,
+                detect_teleports.before(reset_clients),
                 reset_clients.after(init_clients),
                 manage_chunks.after(reset_clients).before(manage_blocks),
                 manage_blocks,
                 record_player_movements.after(manage_blocks),
                 update_replay_npcs.after(record_player_movements),
+                start_watching_champion.after(reset_clients),
+                finish_watching_champion.after(update_replay_npcs),
+                check_chunk_layer_ownership
+                    .after(start_watching_champion)
+                    .after(finish_watching_champion),
                 handle_disconnected_clients,
                 despawn_disconnected_clients,
                 cleanup_ghost_player_list_entries,
                 setup_no_collision_team,
+                update_rank_teams.after(setup_no_collision_team),
+                run_tutorial.after(manage_blocks),
+                publish_champion_status,
+                show_seeds.after(manage_blocks).after(reset_clients),
+                update_afk.before(reset_clients).before(manage_blocks),
                 debug_entity_counts,
             ),
         )
+        .add_systems(
+            Update,
+            (
+                handle_board_command,
+                handle_pause_command,
+                handle_pacer_command,
+                handle_replay_download_command,
+                handle_whereami_command,
+                handle_afk_command,
+                handle_stats_command,
+                handle_ghost_style_command,
+                handle_reload_config_command,
+                handle_race_command,
+                handle_mod_command,
+                handle_preview_command,
+                handle_skip_command,
+                offer_skip.after(manage_blocks),
+                update_tab_list_leaderboard.after(manage_blocks),
+                add_ghosts_to_team.after(update_rank_teams),
+                autosave_scoreboard.after(manage_blocks),
+                show_race_gap.after(manage_blocks).after(update_replay_npcs),
+                clean_up_passed_blocks.after(manage_blocks),
+                broadcast_tips,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                recenter_long_runs
+                    .after(manage_blocks)
+                    .before(clean_up_passed_blocks),
+                handle_top_command,
+                keep_score_visible.after(manage_blocks),
+                handle_sprint_command,
+                handle_blind_command,
+                snapshot_pending_runs.after(manage_blocks),
+                nudge_idle_at_spawn.after(manage_blocks),
+                handle_self_ghost_command,
+                show_kill_plane,
+                handle_kill_plane_command,
+                handle_set_champion_command,
+            ),
+        )
         .run();
 }
 
 #[derive(Debug, Resource)]
 struct Globals {
-    pub scoreboard_layer: Entity,
+    pub boards: HashMap<Board, BoardHandle>,
     pub highscore: Option<HighScore>,
 }
 
 #[derive(Debug, Resource, Default)]
 struct ScoreTracker {
-    pub scores: std::collections::HashMap<String, i32>,
+    pub scores: HashMap<String, i32>,
+    pub best_combos: HashMap<String, i32>,
+    pub runs: HashMap<String, i32>,
+    pub blind_scores: HashMap<String, i32>,
     pub last_saved_top_15: Vec<(String, i32)>,
+    /// Set when the score board's top 15 changed mid-run, until `autosave_scoreboard` writes it.
+    pub top_15_unsaved: bool,
+    /// UUIDs of players who finished the first-join tutorial.
+    pub tutorial_done: HashSet<u128>,
+    /// Whoever set the server's very first record, kept even after they lose it.
+    pub pioneer: Option<String>,
+    pub run_stats: RunStats,
+    /// Blocks cleared by everyone, ever.
+    pub total_jumps: u64,
+    /// When each player reached their current entry on each board, so ties go to whoever got
+    /// there first.
+    pub achieved_at: HashMap<Board, HashMap<String, u128>>,
+    /// Each player's best time in milliseconds for each sprint length.
+    pub sprint_times: HashMap<u32, HashMap<String, u128>>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-struct PlayerMovement {
-    position: [f64; 3],
-    yaw: f32,
-    pitch: f32,
-    timestamp: u128,
-}
+impl ScoreTracker {
+    fn entries(&self, board: Board) -> &HashMap<String, i32> {
+        match board {
+            Board::Score => &self.scores,
+            Board::Combo => &self.best_combos,
+            Board::Runs => &self.runs,
+            Board::Blind => &self.blind_scores,
+        }
+    }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-struct HighScore {
-    username: String,
-    score: u32,
-    seed: u64,
-    movements: Vec<PlayerMovement>,
-}
+    /// Sets `name`'s entry on `board`, reached just now.
+    fn set(&mut self, board: Board, name: &str, value: i32) {
+        let entries = match board {
+            Board::Score => &mut self.scores,
+            Board::Combo => &mut self.best_combos,
+            Board::Runs => &mut self.runs,
+            Board::Blind => &mut self.blind_scores,
+        };
+        entries.insert(name.to_string(), value);
 
-#[derive(Serialize, Deserialize)]
-struct SaveData {
-    highscore: Option<HighScore>,
-    scoreboard: Vec<(String, i32)>,
-}
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        self.achieved_at
+            .entry(board)
+            .or_default()
+            .insert(name.to_string(), now);
+    }
 
-#[derive(Component)]
-struct GameState {
-    blocks: VecDeque<BlockPos>,
-    score: u32,
-    combo: u32,
-    target_y: i32,
-    last_block_timestamp: u128,
-    seed: u64,
-    movements: Vec<PlayerMovement>,
-    movement_start_time: u128,
-    rng: StdRng,
-    recording_started: bool,
-}
+    /// The best 15 entries on `board`. Ties go to whoever reached the value first, then by
+    /// name, so the order never flickers between updates.
+    fn top_15(&self, board: Board) -> Vec<(String, i32)> {
+        self.top_15_since(board, None)
+    }
 
-#[derive(Component)]
-struct ReplayNpc {
-    movements: Vec<PlayerMovement>,
-    current_index: usize,
-    start_time: u128,
-    replay_started: bool,
-    owner_entity: Entity,
-}
+    /// Like `top_15`, but only with entries reached at or after `since` if it's given. Only each
+    /// player's best is known, so someone whose best is older doesn't show up even if they did
+    /// well in that time, and entries saved before times were kept never do.
+    fn top_15_since(&self, board: Board, since: Option<u128>) -> Vec<(String, i32)> {
+        let achieved_at = |name: &str| {
+            self.achieved_at
+                .get(&board)
+                .and_then(|times| times.get(name))
+                .copied()
+                .unwrap_or(u128::MAX)
+        };
 
-#[derive(Component)]
-struct ReplayMode {
-    spawned_npc: Option<Entity>,
-}
+        let mut top: Vec<(String, i32)> = self
+            .entries(board)
+            .iter()
+            .filter(|(name, _)| {
+                // Entries without a time are `u128::MAX`
+                since.is_none_or(|since| (since..u128::MAX).contains(&achieved_at(name)))
+            })
+            .map(|(k, v)| (k.clone(), *v))
+            .collect();
+        top.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then_with(|| achieved_at(&a.0).cmp(&achieved_at(&b.0)))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        top.truncate(15);
+        top
+    }
 
-#[derive(Component)]
-struct NoCollisionTeam;
+    /// The 15 fastest times for sprints to `blocks`, ties by name.
+    fn sprint_top_15(&self, blocks: u32) -> Vec<(String, u128)> {
+        let mut top: Vec<(String, u128)> = self
+            .sprint_times
+            .get(&blocks)
+            .into_iter()
+            .flatten()
+            .map(|(name, time)| (name.clone(), *time))
+            .collect();
+        top.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(15);
+        top
+    }
+}
 
-#[derive(Component)]
-struct GhostPlayerListEntry {
-    ghost_entity: Entity,
+/// How every run so far went, for tuning course generation.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct RunStats {
+    /// Runs by final score, see `SCORE_HISTOGRAM_BUCKET`.
+    score_histogram: Vec<u32>,
+    /// Falls by the jump the player fell on: up a block, level and down.
+    falls_by_jump: [u32; 3],
 }
 
-fn setup(mut commands: Commands, server: Res<Server>) {
-    let parkour_objective_layer = commands.spawn(EntityLayer::new(&server)).id();
-    let mut parkour_objective = ObjectiveBundle {
-        name: Objective::new("parkour-jumps"),
-        display: ObjectiveDisplay("Best scores".into_text()),
-        layer: EntityLayerId(parkour_objective_layer),
-        ..Default::default()
-    };
+impl RunStats {
+    /// Counts a finished run. `failed_jump` is the height difference of the jump it ended on, if
+    /// it ended in a fall.
+    fn record(&mut self, score: u32, failed_jump: Option<i32>) {
+        let bucket = ((score / SCORE_HISTOGRAM_BUCKET) as usize).min(SCORE_HISTOGRAM_BUCKETS - 1);
+        if self.score_histogram.len() <= bucket {
+            self.score_histogram.resize(bucket + 1, 0);
+        }
+        self.score_histogram[bucket] += 1;
 
-    // Load game data from file
-    let (highscore, scoreboard) = match load_game_data() {
-        Ok(save_data) => {
-            if let Some(ref h) = save_data.highscore {
-                println!("Loaded highscore: {} by {}", h.score, h.username);
-            }
-            println!("Loaded {} scoreboard entries", save_data.scoreboard.len());
+        if let Some(dy) = failed_jump {
+            self.falls_by_jump[(1 - dy.signum()) as usize] += 1;
+        }
+    }
 
-            // Populate the objective scores
-            for (name, score) in &save_data.scoreboard {
-                parkour_objective.scores.insert(name.clone(), *score);
+    /// Lowest score at least `fraction` of all runs reached, rounded down to its bucket.
+    fn score_reached_by(&self, fraction: f64) -> u32 {
+        let total: u32 = self.score_histogram.iter().sum();
+        let mut reached = total;
+        for (bucket, runs) in self.score_histogram.iter().enumerate() {
+            reached -= runs;
+            if f64::from(reached) < f64::from(total) * fraction {
+                return bucket as u32 * SCORE_HISTOGRAM_BUCKET;
             }
-
-            (save_data.highscore, save_data.scoreboard)
         }
-        Err(e) => {
-            eprintln!("Failed to load game data: {}", e);
-            (None, Vec::new())
-        }
-    };
+        0
+    }
+}
 
-    commands.spawn(parkour_objective);
+/// Directory everything persisted is written to, set once at startup from `DATA_DIR`.
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
 
-    let globals = Globals {
-        scoreboard_layer: parkour_objective_layer,
-        highscore,
-    };
+/// Path of `file` in the data directory.
+fn data_path(file: &str) -> PathBuf {
+    DATA_DIR
+        .get()
+        .expect("Data directory used before startup")
+        .join(file)
+}
 
-    let mut score_tracker = ScoreTracker::default();
-    for (name, score) in &scoreboard {
-        score_tracker.scores.insert(name.clone(), *score);
+/// Creates `dir` if it's missing and makes sure files can be written to it, so a misconfigured
+/// data directory fails at startup instead of on the first save.
+fn prepare_data_dir(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(".write_test");
+    fs::write(&probe, [])?;
+    fs::remove_file(probe)
+}
+
+/// Re-reads `CONFIG_FILE` and builds the config, jump profile and permissions from it again.
+/// Invalid values are rejected with the message they'd fail startup with, leaving the current
+/// settings alone. Runs in progress keep going, so `MAX_COURSE_BLOCKS` can't drop below
+/// `longest_course`, the most blocks any of them has.
+fn reload_config(longest_course: usize) -> Result<(Config, JumpProfile, Permissions), String> {
+    if std::env::var("CONFIG_FILE").is_err() {
+        return Err(
+            "Settings only come from the environment, set CONFIG_FILE to reload them \
+             without restarting"
+                .to_string(),
+        );
     }
-    score_tracker.last_saved_top_15 = scoreboard;
 
-    commands.insert_resource(globals);
-    commands.insert_resource(score_tracker);
+    let previous = CONFIG_FILE_VARS.lock().unwrap().clone();
+    load_config_file()?;
+
+    let reloaded = Config::from_env().and_then(|config| {
+        if config.max_course_blocks < longest_course {
+            return Err(format!(
+                "MAX_COURSE_BLOCKS must fit the {} block course of a run in progress",
+                longest_course
+            ));
+        }
+        Ok((config, JumpProfile::from_env()?, Permissions::from_env()?))
+    });
+    if reloaded.is_err() {
+        *CONFIG_FILE_VARS.lock().unwrap() = previous;
+    }
+    reloaded
 }
 
-fn init_clients(
-    mut clients: Query<
-        (
-            Entity,
-            &mut Client,
-            &mut VisibleChunkLayer,
-            &mut VisibleEntityLayers,
-            &mut IsFlat,
-            &mut GameMode,
-        ),
-        Added<Client>,
-    >,
-    server: Res<Server>,
-    dimensions: Res<DimensionTypeRegistry>,
-    biomes: Res<BiomeRegistry>,
-    mut commands: Commands,
-    globals: Res<Globals>,
-) {
-    for (
-        entity,
-        mut client,
-        mut visible_chunk_layer,
-        mut visible_entity_layers,
-        mut is_flat,
-        mut game_mode,
-    ) in &mut clients
-    {
-        visible_chunk_layer.0 = entity;
-        visible_entity_layers.0.insert(entity);
-        visible_entity_layers.0.insert(globals.scoreboard_layer);
-        is_flat.0 = true;
-        *game_mode = GameMode::Adventure;
+/// Server settings read from the environment, or `CONFIG_FILE`, at startup.
+#[derive(Clone, Debug, Resource)]
+struct Config {
+    /// Usernames allowed to run operator commands.
+    pub operators: Vec<String>,
+    /// Extra points per cleared block for each point of combo, at most `MAX_COMBO_BONUS`. Off
+    /// (0.0) by default so existing leaderboards stay comparable.
+    pub combo_bonus: f64,
+    /// Shortest time per block a landing may take to keep the combo going, however high it is.
+    pub combo_window_floor_ms: u128,
+    /// Longest time per block a landing may take to keep the combo going, which is the window at
+    /// combo 0. The window shrinks from here as the combo grows.
+    pub combo_window_ceiling_ms: u128,
+    /// Combos at which a run's combo is called out with a short subtitle, in ascending order.
+    /// Off when empty.
+    pub combo_callouts: Vec<u32>,
+    /// Blocks in a player's course at once, the one they stand on included. At least 2, the start
+    /// block and one to jump to. Defaults to 11.
+    pub course_length: usize,
+    /// Most blocks a player's course may hold at once. Courses are `course_length` blocks long in
+    /// normal play, so anything beyond that means blocks aren't being cleaned up.
+    pub max_course_blocks: usize,
+    /// Directory champion replays are exported to as JSON for the web viewer. `/replaydownload`
+    /// is disabled when unset.
+    pub replay_export_dir: Option<PathBuf>,
+    /// How ghosts move between recorded frames.
+    pub replay_smoothing: ReplaySmoothing,
+    /// How ghosts are drawn unless a player picked a style with `/ghoststyle`.
+    pub ghost_style: GhostStyle,
+    /// Whether players joining for the first time are walked through the basics.
+    pub tutorial: bool,
+    /// Scores at which a run is celebrated, in ascending order.
+    pub milestones: Vec<u32>,
+    /// Server list description, `{champion}` and `{score}` are filled in with the current record
+    /// and `{jumps}` with the blocks cleared on the server so far.
+    pub motd: String,
+    /// Chat messages broadcast one at a time, in order, every `tip_interval_ms`. `{champion}` and
+    /// `{score}` are filled in like in the MOTD, tips using them are skipped while there's no
+    /// champion.
+    pub tips: Vec<String>,
+    /// Every 5 minutes by default, off when 0.
+    pub tip_interval_ms: Option<u128>,
+    /// 64x64 PNG shown as the server icon in the server list.
+    pub favicon: Option<PathBuf>,
+    /// Whether players put back on their last block mid-run keep their combo. Strict (off) by
+    /// default.
+    pub keep_combo_on_respawn: bool,
+    /// Lets everyone use debug commands like `/whereami`, not just operators.
+    pub debug_commands: bool,
+    /// Shows a short code for each player's current seed next to their name in the tab list, so
+    /// anyone can check who's playing the same course.
+    pub show_seeds: bool,
+    /// Leaves out frames where the player didn't move or look around, which makes replays of
+    /// runs with pauses on blocks a lot smaller.
+    pub sparse_recording: bool,
+    /// Records whether players were sneaking or sprinting, so ghosts do too. Off by default since
+    /// it makes replays a little bigger.
+    pub ghost_poses: bool,
+    /// Players who haven't moved for this long are marked AFK. Off when unset.
+    pub afk_timeout_ms: Option<u128>,
+    /// Ghosts still waiting for their owner to start after this long are despawned, in case the
+    /// owner desynced. Once a replay starts it ends with its last frame anyway. Every 5 minutes
+    /// by default, off when 0.
+    pub ghost_max_wait_ms: Option<u128>,
+    /// Players who haven't reached a new block for this long mid-run are offered `/skip`, which
+    /// swaps the next block for an easier one. Off when unset.
+    pub stuck_timeout_ms: Option<u128>,
+    /// Players who stay on the start block this long without starting a run are nudged to get
+    /// going. Off when unset.
+    pub spawn_idle_timeout_ms: Option<u128>,
+    /// Always counts the jump from the start block onto the first block, even when the landing
+    /// looks implausible. It's worth a single point, and hesitating on spawn shouldn't cost a
+    /// run its start. On by default.
+    pub first_jump_grace: bool,
+    /// Volume slider every game sound plays under. Players by default, so turning down game
+    /// sounds doesn't mean turning down music from resource packs or other sources.
+    pub sound_category: SoundCategory,
+    /// Shows block break particles and plays a break sound where blocks behind the player
+    /// disappear. Off by default since it sends a few extra packets per landing.
+    pub block_break_effects: bool,
+    /// Asks players to confirm with `/race` before the gold block first starts a race, so
+    /// newcomers stepping on it by accident aren't thrown into one. Instant (off) by default.
+    pub race_confirm: bool,
+    /// Ends races on the gold block at the champion's last block and reports how the player did
+    /// against them, instead of letting the run carry on past it.
+    pub race_finish: bool,
+    /// Lowest champion score the gold block races, so a first record of a couple of blocks
+    /// doesn't make for a pointless race. Any score by default.
+    pub min_race_score: u32,
+    /// Builds each run from one color picked at random when it starts, instead of always
+    /// obsidian. Only the look changes, not the course.
+    pub block_themes: bool,
+    /// How often top 15 changes during runs are written to disk, rather than on every change.
+    /// The sidebar still updates right away. 30 seconds by default.
+    pub scoreboard_save_interval_ms: u128,
+    /// How many blocks behind a player course blocks they got past without landing on are
+    /// removed, as a safety net. Landings already clear everything behind them, so this only
+    /// catches landings that were never registered. 16 by default, 0 turns it off.
+    pub passed_block_cleanup_distance: Option<i32>,
+    /// Strict conditions for every run, so all records were set the same way. Overrides:
+    /// - `/skip` and the stuck hint (`STUCK_TIMEOUT`)
+    /// - `KEEP_COMBO_ON_RESPAWN` and `FIRST_JUMP_GRACE`
+    /// - adaptive courses (`JUMP_ADAPTIVE`)
+    /// - racing the champion on their known course from the gold block
+    /// - racing your own last run on its course again (`/selfghost`)
+    ///
+    /// Off by default.
+    pub hardcore: bool,
+    /// How far from the start, in blocks, a run's course and player are moved back towards it,
+    /// so very long runs don't stream chunks ever further out. Off when unset.
+    pub recenter_distance: Option<i32>,
+    /// How often the score is sent to the action bar again while it's unchanged, so it doesn't
+    /// fade between jumps. Every 2 seconds by default, off when 0.
+    pub action_bar_refresh_ms: Option<u128>,
+    /// Course lengths players can `/sprint`, each with its own leaderboard of times, in
+    /// ascending order. Off when empty.
+    pub sprint_lengths: Vec<u32>,
+    /// How often a run beating the champion is written to `PENDING_RUN_FILE` while it lasts, so
+    /// it survives a crash. Off when unset or 0.
+    pub run_snapshot_interval_ms: Option<u128>,
+    /// Shows new players where they'd lose their run with a faint layer of particles at `KILL_Y`.
+    /// Everyone can switch it with `/killplane`. Off by default.
+    pub kill_plane: bool,
+}
 
-        let seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+/// Interpolation used for ghost positions between recorded frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum ReplaySmoothing {
+    /// Straight lines between frames, exactly as recorded.
+    #[default]
+    Linear,
+    /// A Catmull-Rom spline through the surrounding frames, which rounds off jitter in sparse or
+    /// laggy recordings at the cost of cutting corners slightly.
+    CatmullRom,
+}
 
-        let state = GameState {
-            blocks: VecDeque::new(),
-            score: 0,
-            combo: 0,
-            target_y: 0,
-            last_block_timestamp: 0,
-            seed,
-            movements: Vec::new(),
-            movement_start_time: 0,
-            rng: StdRng::seed_from_u64(seed),
-            recording_started: false,
-        };
+/// How champion ghosts are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum GhostStyle {
+    /// Just a glowing outline, which shows through blocks.
+    #[default]
+    Glow,
+    /// The player model without an outline.
+    Body,
+    /// The player model with a glowing outline.
+    Both,
+}
 
-        let layer = ChunkLayer::new(ident!("the_end"), &dimensions, &biomes, &server);
-        let entity_layer = EntityLayer::new(&server);
+impl GhostStyle {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "glow" => Some(GhostStyle::Glow),
+            "body" => Some(GhostStyle::Body),
+            "both" => Some(GhostStyle::Both),
+            _ => None,
+        }
+    }
 
-        commands
-            .entity(entity)
-            .insert((state, layer, entity_layer, NoCollisionTeam));
+    fn name(self) -> &'static str {
+        match self {
+            GhostStyle::Glow => "glow",
+            GhostStyle::Body => "body",
+            GhostStyle::Both => "both",
+        }
+    }
 
-        // Send welcome message
-        client.send_chat_message("Welcome to Parkour Queue!".color(Color::GOLD).bold());
-        client.send_chat_message(
-            "Jump on the ".color(Color::WHITE)
-                + "GOLD BLOCK".color(Color::GOLD).bold()
-                + " to race against the champion!".color(Color::WHITE),
-        );
-        client.send_chat_message(
-            "The champion's ghost will appear and replay their best run."
-                .italic()
-                .color(Color::GRAY),
-        );
-        client
-            .send_chat_message("Beat their score to become the new champion!".color(Color::GREEN));
+    fn flags(self) -> Flags {
+        let mut flags = Flags::default();
+        flags.set_glowing(self != GhostStyle::Body);
+        flags.set_invisible(self == GhostStyle::Glow);
+        flags
     }
 }
 
-fn reset_clients(
-    mut clients: Query<(
-        Entity,
-        &mut Client,
-        &mut Position,
-        &mut Look,
-        &mut GameState,
-        &mut ChunkLayer,
-        &Username,
-        Option<&ReplayMode>,
-        Option<&Properties>,
-    )>,
-    mut globals: ResMut<Globals>,
-    score_tracker: Res<ScoreTracker>,
-    mut commands: Commands,
-) {
-    for (
-        player_entity,
-        mut client,
-        mut pos,
-        mut look,
-        mut state,
-        mut layer,
-        username,
-        replay_mode,
-        _properties,
-    ) in &mut clients
-    {
-        let out_of_bounds = (pos.0.y as i32) < START_POS.y - 32;
+impl Config {
+    fn from_env() -> Result<Self, String> {
+        let operators = config_var("OPERATORS")
+            .map(|operators| {
+                operators
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
 
-        if out_of_bounds || state.is_added() {
-            if out_of_bounds && !state.is_added() {
-                client.send_chat_message(
-                    "Your score was ".italic()
-                        + state
-                            .score
-                            .to_string()
-                            .color(Color::GOLD)
-                            .bold()
-                            .not_italic(),
-                );
+        let combo_bonus: f64 = parse_var("COMBO_BONUS")?.unwrap_or(0.0);
+        if !(0.0..=MAX_COMBO_BONUS).contains(&combo_bonus) {
+            return Err(format!(
+                "COMBO_BONUS must be between 0 and {}",
+                MAX_COMBO_BONUS
+            ));
+        }
 
-                // Check if this is a new global highscore
-                let is_new_highscore = if let Some(ref existing_highscore) = globals.highscore {
-                    state.score > existing_highscore.score
-                } else {
-                    state.score > 0
-                };
+        let combo_window_floor_ms: u128 = parse_var("COMBO_WINDOW_FLOOR")?.unwrap_or(100);
+        let combo_window_ceiling_ms: u128 = parse_var("COMBO_WINDOW_CEILING")?.unwrap_or(1000);
+        if combo_window_floor_ms == 0 || combo_window_floor_ms > combo_window_ceiling_ms {
+            return Err(
+                "COMBO_WINDOW_FLOOR must be above 0 and at most COMBO_WINDOW_CEILING".to_string(),
+            );
+        }
 
-                if is_new_highscore {
-                    let highscore = HighScore {
-                        username: username.to_string(),
-                        score: state.score,
-                        seed: state.seed,
-                        movements: state.movements.clone(),
-                    };
+        let mut combo_callouts: Vec<u32> = match parse_list_var("COMBO_CALLOUTS")? {
+            Some(callouts) => callouts
+                .into_iter()
+                .filter(|&callout| callout > 0)
+                .collect(),
+            // Where the score changes color
+            None => COMBO_COLORS
+                .iter()
+                .map(|&(combo, _)| combo)
+                .filter(|&combo| combo > 0)
+                .collect(),
+        };
+        combo_callouts.sort_unstable();
+        combo_callouts.dedup();
 
-                    globals.highscore = Some(highscore);
+        let course_length: usize = parse_var("COURSE_LENGTH")?.unwrap_or(BLOCKS_AHEAD + 1);
+        if course_length < 2 {
+            return Err(
+                "COURSE_LENGTH must be at least 2, the start block and one to jump to".to_string(),
+            );
+        }
 
-                    // Get current top 15 from score tracker
-                    let mut current_top_15: Vec<(String, i32)> = score_tracker
-                        .scores
-                        .iter()
-                        .map(|(k, v)| (k.clone(), *v))
-                        .collect();
-                    current_top_15.sort_by(|a, b| b.1.cmp(&a.1));
-                    current_top_15.truncate(15);
+        let max_course_blocks: usize = parse_var("MAX_COURSE_BLOCKS")?.unwrap_or(64);
+        if max_course_blocks < course_length {
+            return Err(format!(
+                "MAX_COURSE_BLOCKS must fit the {} block course",
+                course_length
+            ));
+        }
 
-                    // Save the highscore along with current scoreboard
-                    if let Err(e) = save_game_data(&globals.highscore, &current_top_15) {
-                        eprintln!("Failed to save highscore: {}", e);
-                    }
+        let replay_export_dir = config_var("REPLAY_EXPORT_DIR").ok().map(PathBuf::from);
 
-                    client.send_chat_message(
-                        "NEW GLOBAL HIGHSCORE! ".color(Color::GOLD).bold()
-                            + format!("Score: {} - Your run has been saved!", state.score)
-                                .color(Color::GREEN),
-                    );
-                }
+        let replay_smoothing = match config_var("REPLAY_SMOOTHING").as_deref() {
+            Err(_) | Ok("linear") => ReplaySmoothing::Linear,
+            Ok("catmull-rom") => ReplaySmoothing::CatmullRom,
+            Ok(other) => {
+                return Err(format!(
+                    "Unknown REPLAY_SMOOTHING {:?}, expected linear or catmull-rom",
+                    other
+                ));
             }
+        };
 
-            // Despawn the NPC belonging to this player when they fall
-            if let Some(replay) = replay_mode {
-                if let Some(npc_entity) = replay.spawned_npc {
-                    commands.entity(npc_entity).insert(Despawned);
-                }
-            }
+        let ghost_style = match config_var("GHOST_STYLE") {
+            Ok(style) => GhostStyle::from_name(&style).ok_or_else(|| {
+                format!(
+                    "Unknown GHOST_STYLE {:?}, expected glow, body or both",
+                    style
+                )
+            })?,
+            Err(_) => GhostStyle::Glow,
+        };
 
-            // Remove ReplayMode component if it exists
-            commands.entity(player_entity).remove::<ReplayMode>();
+        let tutorial = config_var("TUTORIAL").is_ok_and(|tutorial| tutorial == "1");
 
-            for pos in ChunkView::new(START_POS.into(), VIEW_DIST).iter() {
-                layer.insert_chunk(pos, UnloadedChunk::new());
-            }
+        let mut milestones: Vec<u32> =
+            parse_list_var("MILESTONES")?.unwrap_or_else(|| vec![25, 50, 100, 250, 500, 1000]);
+        milestones.sort_unstable();
+        milestones.dedup();
 
-            state.score = 0;
-            state.combo = 0;
-            state.seed = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            state.movements.clear();
-            state.movement_start_time = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis();
-            state.rng = StdRng::seed_from_u64(state.seed);
-            state.recording_started = false;
+        let motd = config_var("MOTD")
+            .unwrap_or_else(|_| "Parkour Queue\nChampion: {champion} ({score})".to_string());
+        let favicon = config_var("FAVICON").ok().map(PathBuf::from);
 
-            for block in &state.blocks {
-                layer.set_block(*block, BlockState::AIR);
-            }
-            state.blocks.clear();
-            state.blocks.push_back(START_POS);
-            layer.set_block(START_POS, BlockState::BLACK_WOOL);
+        let tips = match config_var("TIPS") {
+            Ok(tips) => tips
+                .split('|')
+                .map(|tip| tip.trim().to_string())
+                .filter(|tip| !tip.is_empty())
+                .collect(),
+            Err(_) => vec![
+                "Jump on the gold block to race the champion!".to_string(),
+                "Current champion: {champion} with {score}".to_string(),
+                "Step on the diamond block to watch the champion's run.".to_string(),
+            ],
+        };
+        let tip_interval_ms = match parse_var::<u128>("TIP_INTERVAL")? {
+            Some(seconds) => Some(seconds * 1000).filter(|&interval| interval > 0),
+            None => Some(300_000),
+        };
 
-            // Add gold block for pig spawning
-            let gold_block_pos = BlockPos::new(START_POS.x + 2, START_POS.y, START_POS.z);
-            layer.set_block(gold_block_pos, BlockState::GOLD_BLOCK);
+        let ghost_max_wait_ms = match parse_var::<u128>("GHOST_MAX_WAIT")? {
+            Some(seconds) => Some(seconds * 1000).filter(|&wait| wait > 0),
+            None => Some(300_000),
+        };
 
-            for _ in 0..10 {
-                generate_next_block(&mut state, &mut layer, false);
-            }
+        let action_bar_refresh_ms = match parse_var::<u128>("ACTION_BAR_REFRESH")? {
+            Some(seconds) => Some(seconds * 1000).filter(|&interval| interval > 0),
+            None => Some(2000),
+        };
 
-            pos.set([
-                f64::from(START_POS.x) + 0.5,
-                f64::from(START_POS.y) + 1.0,
-                f64::from(START_POS.z) + 0.5,
-            ]);
-            look.yaw = 0.0;
-            look.pitch = 0.0;
-        }
-    }
-}
+        let mut sprint_lengths: Vec<u32> = match parse_list_var("SPRINT_LENGTHS")? {
+            Some(lengths) => lengths.into_iter().filter(|&length| length > 0).collect(),
+            None => vec![25, 50, 100],
+        };
+        sprint_lengths.sort_unstable();
+        sprint_lengths.dedup();
 
-fn manage_blocks(
-    mut clients: Query<(
-        Entity,
-        &mut Client,
-        &mut Position,
-        &mut GameState,
-        &mut ChunkLayer,
-        &Username,
-        Option<&ReplayMode>,
-    )>,
-    mut objectives: Query<&mut ObjectiveScores, With<Objective>>,
-    globals: Res<Globals>,
-    mut score_tracker: ResMut<ScoreTracker>,
-    mut commands: Commands,
-) {
-    for (entity, mut client, mut pos, mut state, mut layer, username, existing_replay_mode) in
-        &mut clients
-    {
-        let pos_under_player = BlockPos::new(
-            (pos.0.x - 0.5).round() as i32,
-            pos.0.y as i32 - 1,
-            (pos.0.z - 0.5).round() as i32,
-        );
+        let run_snapshot_interval_ms = parse_var::<u128>("RUN_SNAPSHOT_INTERVAL")?
+            .map(|seconds| seconds * 1000)
+            .filter(|&interval| interval > 0);
 
-        // Check if player is on the gold block (player spawner)
-        let gold_block_pos = BlockPos::new(START_POS.x + 2, START_POS.y, START_POS.z);
-        if pos_under_player == gold_block_pos {
-            let block_type = layer.block(pos_under_player).unwrap_or_default().state;
-            if block_type == BlockState::GOLD_BLOCK {
-                // Check if there's a global highscore
-                if let Some(highscore) = globals.highscore.clone() {
-                    // Remove any existing NPC for this player
-                    if let Some(replay_mode) = existing_replay_mode {
-                        if let Some(existing_npc) = replay_mode.spawned_npc {
-                            commands.entity(existing_npc).insert(Despawned);
-                        }
-                    }
+        let hardcore = hardcore();
 
-                    // Store original seed and switch to highscore seed
-                    state.seed = highscore.seed;
-                    state.rng = StdRng::seed_from_u64(highscore.seed);
-                    state.score = 0;
-                    // Don't clear movements here - we need them for potential highscore
-                    state.recording_started = false;
+        let recenter_distance: Option<i32> = parse_var("RECENTER_DISTANCE")?;
+        if recenter_distance.is_some_and(|distance| distance <= RECENTER_MARGIN + 16) {
+            return Err(format!(
+                "RECENTER_DISTANCE must be more than {} blocks",
+                RECENTER_MARGIN + 16
+            ));
+        }
 
-                    // Clear and regenerate the parkour with the highscore seed
-                    for block in &state.blocks {
-                        layer.set_block(*block, BlockState::AIR);
-                    }
-                    state.blocks.clear();
-                    state.blocks.push_back(START_POS);
-                    layer.set_block(START_POS, BlockState::BLACK_WOOL);
+        let keep_combo_on_respawn =
+            !hardcore && config_var("KEEP_COMBO_ON_RESPAWN").is_ok_and(|keep| keep == "1");
 
-                    // Keep the gold block
-                    let gold_block_pos = BlockPos::new(START_POS.x + 2, START_POS.y, START_POS.z);
-                    layer.set_block(gold_block_pos, BlockState::GOLD_BLOCK);
+        let debug_commands = config_var("DEBUG_COMMANDS").is_ok_and(|debug| debug == "1");
 
-                    // Generate the same parkour as the highscore run
-                    for _ in 0..10 {
-                        generate_next_block(&mut state, &mut layer, false);
-                    }
+        let show_seeds = config_var("SHOW_SEEDS").is_ok_and(|show| show == "1");
 
-                    // Get the first recorded position from the highscore movements
-                    let (npc_pos, npc_yaw, npc_pitch) =
-                        if let Some(first_movement) = highscore.movements.first() {
-                            (
-                                Position::new(first_movement.position),
-                                first_movement.yaw,
-                                first_movement.pitch,
-                            )
-                        } else {
-                            // Fallback to spawn position if no movements recorded
-                            (
-                                Position::new([
-                                    START_POS.x as f64 + 0.5,
-                                    START_POS.y as f64 + 1.0,
-                                    START_POS.z as f64 + 0.5,
-                                ]),
-                                0.0,
-                                0.0,
-                            )
-                        };
-
-                    let npc_id = UniqueId::default();
-
-                    // Create entity flags with glowing and invisibility
-                    let mut flags = Flags::default();
-                    flags.set_glowing(true);
-                    flags.set_invisible(true);
-
-                    // Spawn the player entity with replay component
-                    let entity_bundle = PlayerEntityBundle {
-                        layer: EntityLayerId(entity),
-                        uuid: npc_id,
-                        position: npc_pos,
-                        look: Look::new(npc_yaw, npc_pitch),
-                        head_yaw: HeadYaw(npc_yaw),
-                        entity_flags: flags,
-                        ..Default::default()
-                    };
+        let sparse_recording = config_var("SPARSE_RECORDING").is_ok_and(|sparse| sparse == "1");
+        let ghost_poses = config_var("GHOST_POSES").is_ok_and(|poses| poses == "1");
 
-                    let replay_component = ReplayNpc {
-                        movements: highscore.movements,
-                        current_index: 0,
-                        start_time: SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_millis(),
-                        replay_started: false,
-                        owner_entity: entity,
-                    };
+        let afk_timeout_ms = parse_var::<u128>("AFK_TIMEOUT")?.map(|seconds| seconds * 1000);
 
-                    let npc_entity = commands
-                        .spawn((
-                            entity_bundle,
-                            replay_component,
-                            GameMode::Spectator,
-                            NoCollisionTeam,
-                        ))
-                        .id();
+        let spawn_idle_timeout_ms =
+            parse_var::<u128>("SPAWN_IDLE_TIMEOUT")?.map(|seconds| seconds * 1000);
 
-                    // Add replay mode component to the player with reference to the spawned NPC
-                    commands.entity(entity).insert(ReplayMode {
-                        spawned_npc: Some(npc_entity),
-                    });
+        let stuck_timeout_ms = parse_var::<u128>("STUCK_TIMEOUT")?
+            .map(|seconds| seconds * 1000)
+            .filter(|_| !hardcore);
 
-                    // Add player list entry so the player is visible
-                    // Truncate username to fit 16 character limit
-                    let ghost_name = if highscore.username.len() > 10 {
-                        format!("{}. Ghost", &highscore.username[..7])
-                    } else {
-                        format!("{} Ghost", &highscore.username)
-                    };
+        let first_jump_grace =
+            !hardcore && !config_var("FIRST_JUMP_GRACE").is_ok_and(|grace| grace == "0");
 
-                    commands.spawn((
-                        PlayerListEntryBundle {
-                            uuid: npc_id,
-                            username: Username(ghost_name.chars().take(16).collect::<String>()),
-                            display_name: DisplayName(
-                                format!("{}'s Ghost ({})", highscore.username, highscore.score)
-                                    .color(Color::GOLD)
-                                    .into(),
-                            ),
-                            listed: Listed(false), // Don't show in player list
-                            ..Default::default()
-                        },
-                        GhostPlayerListEntry {
-                            ghost_entity: npc_entity,
-                        },
-                    ));
-                    
+        let sound_category = match config_var("SOUND_CATEGORY").as_deref() {
+            Err(_) | Ok("player") => SoundCategory::Player,
+            Ok("master") => SoundCategory::Master,
+            Ok("music") => SoundCategory::Music,
+            Ok("record") => SoundCategory::Record,
+            Ok("weather") => SoundCategory::Weather,
+            Ok("block") => SoundCategory::Block,
+            Ok("hostile") => SoundCategory::Hostile,
+            Ok("neutral") => SoundCategory::Neutral,
+            Ok("ambient") => SoundCategory::Ambient,
+            Ok("voice") => SoundCategory::Voice,
+            Ok(other) => {
+                return Err(format!(
+                    "Unknown SOUND_CATEGORY {:?}, expected master, music, record, weather, block, \
+                     hostile, neutral, player, ambient or voice",
+                    other
+                ));
+            }
+        };
 
+        let block_break_effects =
+            config_var("BLOCK_BREAK_EFFECTS").is_ok_and(|effects| effects == "1");
 
-                    client.play_sound(
-                        Sound::EntityPlayerLevelup,
-                        SoundCategory::Master,
-                        pos.0,
-                        1.0,
-                        1.0,
-                    );
+        let race_confirm = config_var("RACE_CONFIRM").is_ok_and(|confirm| confirm == "1");
 
-                    client.send_chat_message(
-                        format!(
-                            "Loading {}'s highscore run (Score: {})!",
-                            highscore.username, highscore.score
-                        )
-                        .color(Color::GOLD),
-                    );
+        let kill_plane = config_var("KILL_PLANE").is_ok_and(|shown| shown == "1");
+        let race_finish = config_var("RACE_FINISH").is_ok_and(|finish| finish == "1");
+        let min_race_score = parse_var("MIN_RACE_SCORE")?.unwrap_or(0);
+        let block_themes = config_var("BLOCK_THEMES").is_ok_and(|themes| themes == "1");
 
-                    // Teleport player back to spawn
-                    pos.set([
-                        START_POS.x as f64 + 0.5,
-                        START_POS.y as f64 + 1.0,
-                        START_POS.z as f64 + 0.5,
-                    ]);
-                } else {
-                    client.send_chat_message("No global highscore recorded yet!".color(Color::RED));
-                }
-            }
-        }
+        let scoreboard_save_interval_ms =
+            parse_var::<u128>("SCOREBOARD_SAVE_INTERVAL")?.map_or(30_000, |seconds| seconds * 1000);
 
-        // Regular parkour logic
-        if let Some(index) = state
-            .blocks
-            .iter()
-            .position(|block| *block == pos_under_player)
-        {
-            if index > 0 {
-                // Start recording when jumping from the first block (index 1, since index 0 is spawn)
-                if !state.recording_started && index == 1 {
-                    state.recording_started = true;
-                    state.movement_start_time = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis();
-                }
-                let power_result = 2_f32.powf((state.combo as f32) / 45.0);
-                let max_time_taken = (1000_f32 * (index as f32) / power_result) as u128;
+        let passed_block_cleanup_distance = match parse_var::<i32>("PASSED_BLOCK_CLEANUP")? {
+            Some(0) => None,
+            Some(distance) => Some(distance),
+            None => Some(16),
+        };
 
-                let current_time_millis = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis();
+        Ok(Config {
+            operators,
+            combo_bonus,
+            combo_window_floor_ms,
+            combo_window_ceiling_ms,
+            combo_callouts,
+            course_length,
+            max_course_blocks,
+            replay_export_dir,
+            replay_smoothing,
+            ghost_style,
+            tutorial,
+            milestones,
+            motd,
+            tips,
+            tip_interval_ms,
+            favicon,
+            keep_combo_on_respawn,
+            debug_commands,
+            show_seeds,
+            sparse_recording,
+            ghost_poses,
+            afk_timeout_ms,
+            ghost_max_wait_ms,
+            stuck_timeout_ms,
+            spawn_idle_timeout_ms,
+            first_jump_grace,
+            sound_category,
+            block_break_effects,
+            race_confirm,
+            race_finish,
+            min_race_score,
+            block_themes,
+            scoreboard_save_interval_ms,
+            passed_block_cleanup_distance,
+            hardcore,
+            recenter_distance,
+            action_bar_refresh_ms,
+            sprint_lengths,
+            run_snapshot_interval_ms,
+            kill_plane,
+        })
+    }
 
-                if current_time_millis - state.last_block_timestamp < max_time_taken {
-                    state.combo += index as u32
-                } else {
-                    state.combo = 0
-                }
+    fn is_operator(&self, username: &str) -> bool {
+        self.operators
+            .iter()
+            .any(|operator| operator.eq_ignore_ascii_case(username))
+    }
+}
 
-                for _ in 0..index {
-                    generate_next_block(&mut state, &mut layer, true)
-                }
+/// Permission nodes granted to players by UUID, on top of `Config::operators` who may do
+/// everything. Loaded from `PERMISSIONS_FILE`, by default `permissions.toml` in the data
+/// directory, which may be missing. Only a small part of TOML is understood:
+///
+/// ```toml
+/// [groups]
+/// admin = ["*"]
+/// mod = ["mod", "pause", "whereami", "preview"]
+///
+/// [players]
+/// "069a79f4-44e9-4726-a5be-fca90e38aaf5" = "mod"
+/// ```
+///
+/// Nodes are named after the command they allow.
+#[derive(Resource, Default)]
+struct Permissions {
+    /// Nodes granted by each group, `*` granting every node.
+    groups: HashMap<String, Vec<String>>,
+    /// Group of each player, by UUID.
+    players: HashMap<u128, String>,
+}
 
-                let pitch = 0.9 + ((state.combo as f32) - 1.0) * 0.05;
-                client.play_sound(
-                    Sound::BlockNoteBlockBass,
-                    SoundCategory::Master,
-                    pos.0,
-                    1.0,
-                    pitch,
-                );
+impl Permissions {
+    fn from_env() -> Result<Self, String> {
+        let path = match config_var("PERMISSIONS_FILE") {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => {
+                let path = data_path("permissions.toml");
+                if !path.exists() {
+                    return Ok(Permissions::default());
+                }
+                path
+            }
+        };
 
-                client.set_action_bar(state.score.to_string().color(Color::LIGHT_PURPLE).bold());
-                let mut objective_mut = objectives.single_mut();
-                let name = username.to_string();
-                let new_score = state.score as i32;
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Permissions::parse(&contents).map_err(|e| format!("{} in {}", e, path.display()))
+    }
 
-                // Update objective scores
-                if let Some(score) = objective_mut.get(&name) {
-                    if *score < new_score {
-                        objective_mut.insert(name.clone(), new_score);
-                    }
-                } else {
-                    objective_mut.insert(name.clone(), new_score);
-                }
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut permissions = Permissions::default();
+        let mut section = None;
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                section = Some(name.trim());
+                continue;
+            }
 
-                // Update score tracker
-                let old_score = score_tracker.scores.get(&name).copied().unwrap_or(0);
-                if new_score > old_score {
-                    score_tracker.scores.insert(name, new_score);
+            let error = |message: &str| format!("{} on line {}", message, number + 1);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("Expected key = value"))?;
+            let key = key.trim().trim_matches('"');
+            let value = value.trim();
 
-                    // Check if top 15 changed
-                    let mut current_top_15: Vec<(String, i32)> = score_tracker
-                        .scores
-                        .iter()
-                        .map(|(k, v)| (k.clone(), *v))
+            match section {
+                Some("groups") => {
+                    let nodes = value
+                        .strip_prefix('[')
+                        .and_then(|value| value.strip_suffix(']'))
+                        .ok_or_else(|| error("Expected a list of nodes"))?;
+                    let nodes = nodes
+                        .split(',')
+                        .map(|node| node.trim().trim_matches('"').to_string())
+                        .filter(|node| !node.is_empty())
                         .collect();
-                    current_top_15.sort_by(|a, b| b.1.cmp(&a.1));
-                    current_top_15.truncate(15);
-
-                    if current_top_15 != score_tracker.last_saved_top_15 {
-                        // Save the updated scoreboard
-                        if let Err(e) = save_game_data(&globals.highscore, &current_top_15) {
-                            eprintln!("Failed to save game data: {}", e);
-                        } else {
-                            score_tracker.last_saved_top_15 = current_top_15;
-                        }
-                    }
+                    permissions.groups.insert(key.to_string(), nodes);
+                }
+                Some("players") => {
+                    let uuid = key.replace('-', "");
+                    let uuid = u128::from_str_radix(&uuid, 16)
+                        .ok()
+                        .filter(|_| uuid.len() == 32)
+                        .ok_or_else(|| error("Expected a UUID"))?;
+                    permissions
+                        .players
+                        .insert(uuid, value.trim_matches('"').to_string());
                 }
+                _ => return Err(error("Expected [groups] or [players] first")),
             }
         }
-    }
-}
-
-fn manage_chunks(mut clients: Query<(&Position, &OldPosition, &mut ChunkLayer), With<Client>>) {
-    for (pos, old_pos, mut layer) in &mut clients {
-        let old_view = ChunkView::new(old_pos.get().into(), VIEW_DIST);
-        let view = ChunkView::new(pos.0.into(), VIEW_DIST);
-
-        if old_view != view {
-            for pos in old_view.diff(view) {
-                layer.remove_chunk(pos);
-            }
 
-            for pos in view.diff(old_view) {
-                layer.chunk_entry(pos).or_default();
-            }
+        if let Some(group) = permissions
+            .players
+            .values()
+            .find(|group| !permissions.groups.contains_key(*group))
+        {
+            return Err(format!("Unknown group {:?}", group));
         }
+        Ok(permissions)
     }
-}
 
-fn generate_next_block(state: &mut GameState, layer: &mut ChunkLayer, in_game: bool) {
-    if in_game {
-        let removed_block = state.blocks.pop_front().unwrap();
-        layer.set_block(removed_block, BlockState::AIR);
-
-        state.score += 1
+    /// Whether a player may use the command behind `node`, as an operator or through their
+    /// group. Every command handler checks this the same way.
+    fn allows(&self, config: &Config, username: &Username, uuid: &UniqueId, node: &str) -> bool {
+        config.is_operator(&username.0) || self.has_permission(uuid.0.as_u128(), node)
     }
 
-    let last_pos = *state.blocks.back().unwrap();
-    let block_pos = generate_random_block(last_pos, state.target_y, &mut state.rng);
-
-    if last_pos.y == START_POS.y {
-        state.target_y = 0
-    } else if last_pos.y < START_POS.y - 30 || last_pos.y > START_POS.y + 30 {
-        state.target_y = START_POS.y;
+    fn has_permission(&self, uuid: u128, node: &str) -> bool {
+        self.players
+            .get(&uuid)
+            .and_then(|group| self.groups.get(group))
+            .is_some_and(|nodes| {
+                nodes
+                    .iter()
+                    .any(|granted| granted == "*" || granted == node)
+            })
     }
+}
 
-    layer.set_block(block_pos, *BLOCK_TYPES.choose(&mut state.rng).unwrap());
-    state.blocks.push_back(block_pos);
+/// The current champion's name and score, shared with the server list ping callback.
+#[derive(Resource, Default)]
+struct ChampionStatus(Arc<Mutex<Option<(String, u32)>>>);
 
-    state.last_block_timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis();
+/// `ScoreTracker::total_jumps`, shared with the server list ping callback.
+#[derive(Resource, Default)]
+struct JumpCounter(Arc<AtomicU64>);
+
+/// Answers server list pings with the configured MOTD and the current champion.
+struct Callbacks {
+    motd: String,
+    favicon: Vec<u8>,
+    champion: Arc<Mutex<Option<(String, u32)>>>,
+    total_jumps: Arc<AtomicU64>,
 }
 
-fn generate_random_block(pos: BlockPos, target_y: i32, rng: &mut StdRng) -> BlockPos {
-    let y = match target_y {
-        0 => rng.random_range(-1..2),
-        y if y > pos.y => 1,
-        _ => -1,
-    };
-    let z = match y {
-        1 => rng.random_range(1..3),
-        -1 => rng.random_range(2..5),
-        _ => rng.random_range(1..4),
-    };
-    let x = rng.random_range(-3..4);
+#[async_trait]
+impl NetworkCallbacks for Callbacks {
+    async fn server_list_ping(
+        &self,
+        shared: &SharedNetworkState,
+        _remote_addr: SocketAddr,
+        _handshake_data: &HandshakeData,
+    ) -> ServerListPing {
+        let champion = self.champion.lock().unwrap().clone();
+        let (name, score) = match &champion {
+            Some((name, score)) => (name.as_str(), score.to_string()),
+            None => ("nobody yet", "0".to_string()),
+        };
 
-    BlockPos::new(pos.x + x, pos.y + y, pos.z + z)
+        ServerListPing::Respond {
+            online_players: shared.player_count().load(Ordering::Relaxed) as i32,
+            max_players: shared.max_players() as i32,
+            // Shows up when hovering the player count in the server list
+            player_sample: vec![PlayerSampleEntry {
+                name: format!("Champion: {} ({})", name, score),
+                id: Uuid::nil(),
+            }],
+            description: self
+                .motd
+                .replace("{champion}", name)
+                .replace("{score}", &score)
+                .replace(
+                    "{jumps}",
+                    &thousands(self.total_jumps.load(Ordering::Relaxed)),
+                )
+                .into_text(),
+            favicon_png: &self.favicon,
+            version_name: MINECRAFT_VERSION.to_string(),
+            protocol: PROTOCOL_VERSION,
+        }
+    }
 }
 
-fn record_player_movements(mut clients: Query<(&Position, &Look, &mut GameState), With<Client>>) {
-    for (pos, look, mut state) in &mut clients {
-        if state.recording_started {
-            let current_time = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis();
+/// Set while an operator has paused all runs, holding the time the pause started.
+#[derive(Debug, Resource, Default)]
+struct Paused {
+    pub started_at: Option<u128>,
+}
 
-            let movement = PlayerMovement {
-                position: [pos.0.x, pos.0.y, pos.0.z],
-                yaw: look.yaw,
-                pitch: look.pitch,
-                timestamp: current_time - state.movement_start_time,
-            };
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Board {
+    Score,
+    Combo,
+    Runs,
+    Blind,
+}
 
-            state.movements.push(movement);
-            
-            // Limit movements to prevent unbounded memory growth
-            // 30 minutes of gameplay at 20 TPS = 36000 movements
-            const MAX_MOVEMENTS: usize = 36000;
-            if state.movements.len() > MAX_MOVEMENTS {
-                // Remove oldest movements
-                let excess = state.movements.len() - MAX_MOVEMENTS;
-                state.movements.drain(0..excess);
+impl Board {
+    const ALL: [Board; 4] = [Board::Score, Board::Combo, Board::Runs, Board::Blind];
 
-            }
+    fn objective_name(self) -> &'static str {
+        match self {
+            Board::Score => "parkour-jumps",
+            Board::Combo => "parkour-combo",
+            Board::Runs => "parkour-runs",
+            Board::Blind => "parkour-blind",
         }
     }
-}
 
-fn update_replay_npcs(
-    mut npcs: Query<(
-        Entity,
-        &mut Position,
-        &mut Look,
-        &mut HeadYaw,
-        &mut ReplayNpc,
-    )>,
-    clients: Query<&GameState>,
-    mut commands: Commands,
-) {
-    // Since we only have one NPC at a time, we can use single() or iter().next()
-    for (entity, mut pos, mut look, mut head_yaw, mut replay) in &mut npcs {
-        // Check if the owner player has started playing (score >= 1)
-        if let Ok(owner_state) = clients.get(replay.owner_entity) {
-            if owner_state.score > 0 && !replay.replay_started {
-                // Player just started, begin the replay
-                replay.replay_started = true;
-                replay.start_time = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis();
-            }
+    fn title(self) -> &'static str {
+        match self {
+            Board::Score => "Best scores",
+            Board::Combo => "Best combos",
+            Board::Runs => "Runs played",
+            Board::Blind => "Best blind scores",
         }
+    }
 
-        // If replay hasn't started yet, keep NPC at first position
-        if !replay.replay_started {
-            continue;
+    fn from_name(name: &str) -> Option<Board> {
+        match name.to_lowercase().as_str() {
+            "score" | "scores" => Some(Board::Score),
+            "combo" | "combos" => Some(Board::Combo),
+            "runs" => Some(Board::Runs),
+            "blind" => Some(Board::Blind),
+            _ => None,
         }
-        // Check if movements vector is empty
-        if replay.movements.is_empty() {
-            commands.entity(entity).insert(Despawned);
-            continue;
+    }
+
+    fn next(self) -> Board {
+        match self {
+            Board::Score => Board::Combo,
+            Board::Combo => Board::Runs,
+            Board::Runs => Board::Blind,
+            Board::Blind => Board::Score,
         }
+    }
+}
 
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
+/// The objective entity of a board and the layer it is displayed on.
+#[derive(Clone, Copy, Debug)]
+struct BoardHandle {
+    objective: Entity,
+    layer: Entity,
+}
 
-        let elapsed = current_time.saturating_sub(replay.start_time);
+/// A recorded frame of a run. Stored with `encode_frames`, so fields must only ever be appended.
+#[derive(Clone, Debug, PartialEq)]
+struct PlayerMovement {
+    position: [f64; 3],
+    yaw: f32,
+    pitch: f32,
+    timestamp: u128,
+    /// Only recorded with `Config::ghost_poses`.
+    sneaking: bool,
+    sprinting: bool,
+}
 
-        // Find the appropriate movement frame
-        while replay.current_index < replay.movements.len().saturating_sub(1) {
-            if replay.movements[replay.current_index + 1].timestamp <= elapsed {
-                replay.current_index += 1;
-            } else {
-                break;
-            }
-        }
+/// Layout frames had when they were stored directly in `HighScore`. Saves from before frames
+/// were versioned still hold these, and `run_hash` is over them so it never changes.
+#[derive(Serialize, Deserialize)]
+struct LegacyFrame {
+    position: [f64; 3],
+    yaw: f32,
+    pitch: f32,
+    timestamp: u128,
+}
 
-        if replay.current_index >= replay.movements.len() {
-            // Replay finished, despawn the NPC
-            commands.entity(entity).insert(Despawned);
-            continue;
+impl From<&PlayerMovement> for LegacyFrame {
+    fn from(movement: &PlayerMovement) -> Self {
+        LegacyFrame {
+            position: movement.position,
+            yaw: movement.yaw,
+            pitch: movement.pitch,
+            timestamp: movement.timestamp,
         }
+    }
+}
+
+impl From<LegacyFrame> for PlayerMovement {
+    fn from(frame: LegacyFrame) -> Self {
+        PlayerMovement {
+            position: frame.position,
+            yaw: frame.yaw,
+            pitch: frame.pitch,
+            timestamp: frame.timestamp,
+            sneaking: false,
+            sprinting: false,
+        }
+    }
+}
+
+fn serialize_legacy_frames<S: Serializer>(
+    movements: &[PlayerMovement],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(movements.iter().map(LegacyFrame::from))
+}
+
+fn deserialize_legacy_frames<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<PlayerMovement>, D::Error> {
+    let frames = Vec::<LegacyFrame>::deserialize(deserializer)?;
+    Ok(frames.into_iter().map(PlayerMovement::from).collect())
+}
+
+/// A run as exported for the web replay viewer. This is a public format, so fields must not be
+/// renamed or removed without bumping `version`.
+#[derive(Serialize)]
+struct ReplayExport {
+    version: u32,
+    username: String,
+    score: u32,
+    seed: u64,
+    /// Every block of the course in order, starting with the start block.
+    blocks: Vec<[i32; 3]>,
+    frames: Vec<ReplayExportFrame>,
+    /// `run_hash` of the run as hex, so edited exports can be told apart from real runs.
+    hash: String,
+}
+
+#[derive(Serialize)]
+struct ReplayExportFrame {
+    pos: [f64; 3],
+    yaw: f32,
+    pitch: f32,
+    /// Milliseconds since the start of the run.
+    timestamp: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct HighScore {
+    username: String,
+    score: u32,
+    seed: u64,
+    /// Saved as `SaveData::highscore_frames`, only saves from before then have them here.
+    #[serde(
+        serialize_with = "serialize_legacy_frames",
+        deserialize_with = "deserialize_legacy_frames"
+    )]
+    movements: Vec<PlayerMovement>,
+    /// Every block of the run from the start block on, so races and replays don't depend on the
+    /// generator staying the same. Persisted as `SaveData::highscore_course`, since changing how
+    /// `HighScore` is encoded would break existing save files.
+    #[serde(skip)]
+    course: Vec<BlockPos>,
+    /// Index in `course` of the last block the run landed on, where races against it finish.
+    /// Persisted as `SaveData::highscore_reached`.
+    #[serde(skip)]
+    reached: usize,
+}
+
+/// Persisted game data. New fields must only ever be appended, see `load_game_data`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default)]
+struct SaveData {
+    highscore: Option<HighScore>,
+    scoreboard: Vec<(String, i32)>,
+    combo_board: Vec<(String, i32)>,
+    runs_board: Vec<(String, i32)>,
+    tutorial_done: Vec<u128>,
+    highscore_course: Vec<[i32; 3]>,
+    pioneer: Option<String>,
+    /// `run_hash` of `highscore`, missing in saves written before it was added.
+    highscore_hash: Option<u64>,
+    run_stats: RunStats,
+    total_jumps: u64,
+    /// `ScoreTracker::achieved_at` for the saved entries of each board, in `Board::ALL` order.
+    achieved_at: Vec<Vec<(String, u128)>>,
+    /// `encode_frames` of the highscore's movements.
+    highscore_frames: Vec<u8>,
+    /// `ScoreTracker::sprint_top_15` of each sprint length.
+    sprint_times: Vec<(u32, Vec<(String, u128)>)>,
+    blind_board: Vec<(String, i32)>,
+    /// `HighScore::reached`, missing in saves written before it was added.
+    highscore_reached: Option<u64>,
+}
+
+#[derive(Component)]
+struct GameState {
+    blocks: CourseBlocks,
+    score: u32,
+    combo: u32,
+    target_y: i32,
+    last_block_timestamp: u128,
+    seed: u64,
+    movements: Vec<PlayerMovement>,
+    /// Latest frame left out of a sparse recording, see `record_player_movements`.
+    held_frame: Option<PlayerMovement>,
+    movement_start_time: u128,
+    rng: StdRng,
+    recording_started: bool,
+    suspicious_movement: bool,
+    /// Highest milestone this run has been celebrated for.
+    last_milestone: u32,
+    /// When the player last moved, and where to, for detecting inactivity.
+    last_active: u128,
+    last_position: [f64; 3],
+    /// Time per block of the player's most recent jumps, kept across runs.
+    recent_jump_times: VecDeque<u128>,
+    /// Every block placed since the run started, from the start block on.
+    course: Vec<BlockPos>,
+    /// Index in `course` of the last block the player landed on. Blocks cleaned up behind them
+    /// without a landing don't move it.
+    reached: usize,
+    /// Blocks to place next instead of generating new ones, for rebuilding a stored course.
+    planned_blocks: VecDeque<BlockPos>,
+    /// Ghost style the player picked with `/ghoststyle`, if any.
+    ghost_style: Option<GhostStyle>,
+    /// Whether the player confirmed racing from the gold block with `/race` this session, and
+    /// whether they've been asked to.
+    race_confirmed: bool,
+    race_prompted: bool,
+    /// `last_block_timestamp` the player was last offered `/skip` at, so it's offered once per
+    /// block.
+    skip_offered_for: u128,
+    /// `last_block_timestamp` the player was last nudged to start a run at, so it's once per
+    /// reset.
+    idle_nudged_for: u128,
+    /// Block this run's course is built from.
+    block: BlockState,
+    /// How far the course was moved back towards the start along x and z. The course and
+    /// recorded movements are kept where they'd be without moving it.
+    frame_offset: (i32, i32),
+}
+
+impl GameState {
+    fn new(seed: u64) -> Self {
+        GameState {
+            blocks: CourseBlocks::default(),
+            score: 0,
+            combo: 0,
+            target_y: 0,
+            last_block_timestamp: 0,
+            seed,
+            movements: Vec::new(),
+            held_frame: None,
+            movement_start_time: 0,
+            rng: StdRng::seed_from_u64(seed),
+            recording_started: false,
+            suspicious_movement: false,
+            last_milestone: 0,
+            last_active: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis(),
+            last_position: [0.0; 3],
+            recent_jump_times: VecDeque::new(),
+            course: Vec::new(),
+            reached: 0,
+            planned_blocks: VecDeque::new(),
+            ghost_style: None,
+            race_confirmed: false,
+            race_prompted: false,
+            skip_offered_for: 0,
+            idle_nudged_for: 0,
+            block: BlockState::OBSIDIAN,
+            frame_offset: (0, 0),
+        }
+    }
+
+    /// Where `pos` would be if the course was never moved back towards the start.
+    fn unshifted(&self, pos: BlockPos) -> BlockPos {
+        BlockPos::new(
+            pos.x + self.frame_offset.0,
+            pos.y,
+            pos.z + self.frame_offset.1,
+        )
+    }
+
+    /// Starts recording on the run's first landing, with frames timed from `now`. That may be
+    /// past block 1 if the player skipped ahead or didn't register on it, so this doesn't check
+    /// which block it was.
+    fn start_recording(&mut self, now: u128) {
+        if !self.recording_started {
+            self.recording_started = true;
+            self.movement_start_time = now;
+        }
+    }
+
+    /// Holds `movement` back if nothing changed since the last recorded frame, for sparse
+    /// recording. Held back frames are only recorded right before the player moves again, so the
+    /// ghost waits in place instead of drifting towards the next position for the whole pause.
+    /// Returns `movement` if it should be recorded, after recording the held back frame.
+    fn hold_redundant_frame(&mut self, movement: PlayerMovement) -> Option<PlayerMovement> {
+        if self
+            .movements
+            .last()
+            .is_some_and(|previous| is_redundant_frame(previous, &movement))
+        {
+            self.held_frame = Some(movement);
+            return None;
+        }
+        self.movements.extend(self.held_frame.take());
+        Some(movement)
+    }
+
+    /// Takes the run's frames so far, including a held back one, without copying them. Only
+    /// meant for when the run is over.
+    fn take_recorded_movements(&mut self) -> Vec<PlayerMovement> {
+        let mut movements = mem::take(&mut self.movements);
+        movements.extend(self.held_frame.take());
+        movements
+    }
+
+    /// A copy of the run's frames so far, including a held back one, for a run that goes on.
+    fn recorded_movements(&self) -> Vec<PlayerMovement> {
+        let mut movements = self.movements.clone();
+        movements.extend(self.held_frame.clone());
+        movements
+    }
+
+    fn record_jump_time(&mut self, time_per_block: u128) {
+        self.recent_jump_times.push_back(time_per_block);
+        if self.recent_jump_times.len() > SKILL_WINDOW {
+            self.recent_jump_times.pop_front();
+        }
+    }
+
+    /// Hardest jump, as a fraction of the longest reachable gap, to generate for this player.
+    /// Players without any jumps yet are treated as fast so fresh courses aren't held back.
+    fn max_jump_hardness(&self) -> f64 {
+        if self.recent_jump_times.is_empty() {
+            return f64::INFINITY;
+        }
+
+        let average = self.recent_jump_times.iter().sum::<u128>() as f64
+            / self.recent_jump_times.len() as f64;
+        let speed = ((SKILL_SLOW_JUMP_MS - average) / (SKILL_SLOW_JUMP_MS - SKILL_FAST_JUMP_MS))
+            .clamp(0.0, 1.0);
+        MIN_ADAPTIVE_HARDNESS + (1.0 - MIN_ADAPTIVE_HARDNESS) * speed
+    }
+}
+
+#[derive(Component)]
+struct ReplayNpc {
+    movements: Vec<PlayerMovement>,
+    current_index: usize,
+    start_time: u128,
+    replay_started: bool,
+    owner_entity: Entity,
+    spawned_at: u128,
+    distance_since_step_sound: f64,
+}
+
+#[derive(Component)]
+struct ReplayMode {
+    spawned_npc: Option<Entity>,
+    /// Index in the course of the last block the champion reached.
+    finish_index: usize,
+}
+
+/// Present on players who reached the end of the champion's run with `Config::race_finish` on.
+/// Their run ends on the next reset, like after a fall.
+#[derive(Component)]
+struct FinishedRace;
+
+/// Present on players watching the champion's run from the diamond block.
+#[derive(Component)]
+struct Watching {
+    ghost: Entity,
+    course_layer: Entity,
+}
+
+/// Marks a ghost that starts over instead of despawning when its replay ends.
+#[derive(Component)]
+struct LoopingReplay;
+
+/// Present on players who have the champion's ghost running alongside them as a pacer.
+#[derive(Component)]
+struct Pacer {
+    ghost: Entity,
+}
+
+/// Present on players racing their own last run with `/selfghost`, with the ghost of it if one is
+/// out.
+#[derive(Component)]
+struct SelfGhost {
+    ghost: Option<Entity>,
+}
+
+/// Each player's last run while they race themselves, by UUID so it's still there after a
+/// reconnect. Only kept in memory, frames of every player's runs would bloat the save file.
+#[derive(Resource, Default)]
+struct LastRuns(HashMap<u128, HighScore>);
+
+/// Present on players going through the first-join tutorial.
+#[derive(Component)]
+struct Tutorial {
+    /// Index of the next step in `TUTORIAL_STEPS` to show.
+    step: usize,
+}
+
+/// Present on players who are away. Their run is frozen until they move off the spot they went
+/// AFK on.
+#[derive(Component)]
+struct Afk {
+    since: u128,
+    position: [f64; 3],
+}
+
+/// Present on operators flying around in `/mod`. Their `GameState` is parked here, so nothing
+/// treats them as playing until `/play` puts it back.
+#[derive(Component)]
+struct Moderating {
+    state: GameState,
+}
+
+/// Present while a player sprints to `blocks` cleared blocks with `/sprint`. Their time is taken
+/// when they get there, and the run ends on the next tick.
+#[derive(Component)]
+struct Sprint {
+    blocks: u32,
+    finished: bool,
+}
+
+/// Present on players who switched to blind mode with `/blind`, where only the next block of the
+/// course is placed. Kept across runs until they switch back.
+#[derive(Component)]
+struct Blind;
+
+/// Present on players who see the kill plane, with when its particles were last sent.
+#[derive(Component)]
+struct KillPlane {
+    shown_at: u128,
+}
+
+/// The seed whose code is currently shown next to a player's name in the tab list.
+#[derive(Component)]
+struct ShownSeed(u64);
+
+#[derive(Component)]
+struct SelectedBoard(Board);
+
+#[derive(Component)]
+struct NoCollisionTeam;
+
+#[derive(Component)]
+struct GhostPlayerListEntry {
+    ghost_entity: Entity,
+}
+
+fn setup(mut commands: Commands, server: Res<Server>) {
+    // Load game data from file
+    let mut save_data = match load_game_data() {
+        Ok(save_data) => {
+            if let Some(ref h) = save_data.highscore {
+                println!("Loaded highscore: {} by {}", h.score, h.username);
+            }
+            println!("Loaded {} scoreboard entries", save_data.scoreboard.len());
+            save_data
+        }
+        Err(e) => {
+            eprintln!("Failed to load game data: {}", e);
+            SaveData::default()
+        }
+    };
+
+    let mut score_tracker = ScoreTracker::default();
+    for (name, score) in &save_data.scoreboard {
+        score_tracker.scores.insert(name.clone(), *score);
+    }
+    for (name, combo) in &save_data.combo_board {
+        score_tracker.best_combos.insert(name.clone(), *combo);
+    }
+    for (name, runs) in &save_data.runs_board {
+        score_tracker.runs.insert(name.clone(), *runs);
+    }
+    for (name, score) in &save_data.blind_board {
+        score_tracker.blind_scores.insert(name.clone(), *score);
+    }
+    for (board, times) in Board::ALL.into_iter().zip(save_data.achieved_at) {
+        score_tracker
+            .achieved_at
+            .insert(board, times.into_iter().collect());
+    }
+    score_tracker.last_saved_top_15 = save_data.scoreboard;
+    score_tracker.tutorial_done = save_data.tutorial_done.into_iter().collect();
+    score_tracker.pioneer = save_data.pioneer;
+    score_tracker.run_stats = save_data.run_stats;
+    score_tracker.total_jumps = save_data.total_jumps;
+    for (blocks, times) in save_data.sprint_times {
+        score_tracker
+            .sprint_times
+            .insert(blocks, times.into_iter().collect());
+    }
+
+    // A run that was beating the champion when the server went down becomes the champion's run,
+    // unless a better one was saved after it
+    let pending_path = data_path(PENDING_RUN_FILE);
+    let recovered = match read_save_data(&pending_path) {
+        Ok(SaveData {
+            highscore: Some(run),
+            ..
+        }) if beats_highscore(&save_data.highscore, run.score) => {
+            println!(
+                "Recovered unfinished run by {} with score {}",
+                run.username, run.score
+            );
+            if save_data.highscore.is_none() && score_tracker.pioneer.is_none() {
+                score_tracker.pioneer = Some(run.username.clone());
+            }
+            save_data.highscore = Some(run);
+            true
+        }
+        Ok(_) => false,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", pending_path.display(), e);
+            false
+        }
+    };
+
+    let mut boards = HashMap::new();
+    for board in Board::ALL {
+        let layer = commands.spawn(EntityLayer::new(&server)).id();
+        let mut objective = ObjectiveBundle {
+            name: Objective::new(board.objective_name()),
+            display: ObjectiveDisplay(board.title().into_text()),
+            layer: EntityLayerId(layer),
+            ..Default::default()
+        };
+
+        // Populate the objective scores. Like `show_top_15`, only the top 15 go on it; the rest
+        // stay in `ScoreTracker`
+        for (name, score) in score_tracker.top_15(board) {
+            objective.scores.insert(name, score);
+        }
+
+        let objective = commands.spawn(objective).id();
+        boards.insert(board, BoardHandle { objective, layer });
+    }
+
+    let globals = Globals {
+        boards,
+        highscore: save_data.highscore,
+    };
+
+    // Only drop the snapshot once the run it held is saved for good
+    if recovered {
+        if let Err(e) = save_game_data(&globals.highscore, &score_tracker) {
+            eprintln!("Failed to save game data: {}", e);
+        } else if let Err(e) = fs::remove_file(&pending_path) {
+            eprintln!("Failed to remove {}: {}", pending_path.display(), e);
+        }
+    }
+
+    commands.insert_resource(globals);
+    commands.insert_resource(score_tracker);
+}
+
+fn init_clients(
+    mut clients: Query<
+        (
+            Entity,
+            &mut Client,
+            &mut VisibleChunkLayer,
+            &mut VisibleEntityLayers,
+            &mut IsFlat,
+            &mut GameMode,
+            &UniqueId,
+            &Username,
+        ),
+        Added<Client>,
+    >,
+    server: Res<Server>,
+    dimensions: Res<DimensionTypeRegistry>,
+    biomes: Res<BiomeRegistry>,
+    mut commands: Commands,
+    globals: Res<Globals>,
+    score_tracker: Res<ScoreTracker>,
+    config: Res<Config>,
+) {
+    for (
+        entity,
+        mut client,
+        mut visible_chunk_layer,
+        mut visible_entity_layers,
+        mut is_flat,
+        mut game_mode,
+        uuid,
+        username,
+    ) in &mut clients
+    {
+        visible_chunk_layer.0 = entity;
+        visible_entity_layers.0.insert(entity);
+        visible_entity_layers
+            .0
+            .insert(globals.boards[&Board::Score].layer);
+        is_flat.0 = true;
+        // Every connection starts a fresh run, whatever mode the player left in. Their old
+        // entity is gone along with its components, and `handle_disconnected_clients` and
+        // `update_replay_npcs` clean up the ghosts and layers it had.
+        *game_mode = GameMode::Adventure;
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let state = GameState::new(seed);
+
+        let layer = ChunkLayer::new(ident!("the_end"), &dimensions, &biomes, &server);
+        let entity_layer = EntityLayer::new(&server);
+
+        commands.entity(entity).insert((
+            state,
+            layer,
+            entity_layer,
+            NoCollisionTeam,
+            SelectedBoard(Board::Score),
+        ));
+        if config.kill_plane {
+            commands.entity(entity).insert(KillPlane { shown_at: 0 });
+        }
+
+        // Send welcome message
+        client.send_chat_message("Welcome to Parkour Queue!".color(Color::GOLD).bold());
+        client.send_chat_message(
+            "Jump on the ".color(Color::WHITE)
+                + "GOLD BLOCK".color(Color::GOLD).bold()
+                + " to race against the champion!".color(Color::WHITE),
+        );
+        client.send_chat_message(
+            "The champion's ghost will appear and replay their best run."
+                .italic()
+                .color(Color::GRAY),
+        );
+        client
+            .send_chat_message("Beat their score to become the new champion!".color(Color::GREEN));
+        client.send_chat_message(
+            "Or step on the ".color(Color::WHITE)
+                + "DIAMOND BLOCK".color(Color::AQUA).bold()
+                + " to just watch the record run.".color(Color::WHITE),
+        );
+
+        if score_tracker.pioneer.as_ref().is_some_and(|pioneer| {
+            // Usernames are only unique case-insensitively
+            pioneer.eq_ignore_ascii_case(&username.0)
+        }) {
+            client.send_chat_message(
+                "[Pioneer] ".color(Color::AQUA).bold()
+                    + "Welcome back! You set the very first record here.".color(Color::WHITE),
+            );
+        }
+
+        if config.tutorial && !score_tracker.tutorial_done.contains(&uuid.0.as_u128()) {
+            commands.entity(entity).insert(Tutorial { step: 0 });
+        }
+    }
+}
+
+fn reset_clients(
+    mut clients: Query<
+        (
+            Entity,
+            &mut Client,
+            &mut Position,
+            &mut Look,
+            &mut GameState,
+            &mut ChunkLayer,
+            &Username,
+            Option<&ReplayMode>,
+            Option<&Properties>,
+            Has<FinishedRace>,
+            Option<&Sprint>,
+            Has<Blind>,
+            &UniqueId,
+            Option<&mut SelfGhost>,
+        ),
+        Without<Watching>,
+    >,
+    mut objectives: Query<&mut ObjectiveScores, With<Objective>>,
+    mut globals: ResMut<Globals>,
+    mut score_tracker: ResMut<ScoreTracker>,
+    mut last_runs: ResMut<LastRuns>,
+    profile: Res<JumpProfile>,
+    config: Res<Config>,
+    special_blocks: Res<SpecialBlocks>,
+    paused: Res<Paused>,
+    uuids: Query<&UniqueId>,
+    mut commands: Commands,
+) {
+    for (
+        player_entity,
+        mut client,
+        mut pos,
+        mut look,
+        mut state,
+        mut layer,
+        username,
+        replay_mode,
+        _properties,
+        finished_race,
+        sprint,
+        blind,
+        uuid,
+        mut self_ghost,
+    ) in &mut clients
+    {
+        let fell = (pos.0.y as i32) < KILL_Y;
+        let out_of_bounds = fell || finished_race || sprint.is_some_and(|sprint| sprint.finished);
+
+        // Nobody loses their run to a fall while the game is paused, put them back on their
+        // block. Finished races and sprints still end as usual
+        if fell && !state.is_added() && paused.started_at.is_some() {
+            let block = state.blocks[0];
+            pos.set([
+                f64::from(block.x) + 0.5,
+                f64::from(block.y) + 1.0,
+                f64::from(block.z) + 0.5,
+            ]);
+            continue;
+        }
+
+        if out_of_bounds || state.is_added() {
+            // Keep the run for a player racing themselves, before a highscore takes its frames
+            if self_ghost.is_some() && out_of_bounds && !state.is_added() && state.score > 0 {
+                last_runs.0.insert(
+                    uuid.0.as_u128(),
+                    HighScore {
+                        username: username.to_string(),
+                        score: state.score,
+                        seed: state.seed,
+                        movements: state.recorded_movements(),
+                        course: state.course.clone(),
+                        reached: state.reached,
+                    },
+                );
+            }
+
+            // Sprints are timed instead of scored, finished ones were reported on their last block
+            if out_of_bounds && !state.is_added() && sprint.is_some() {
+                if let Some(sprint) = sprint.filter(|sprint| !sprint.finished) {
+                    client.send_chat_message(
+                        format!(
+                            "DNF - you fell after {} of {} blocks.",
+                            state.reached, sprint.blocks
+                        )
+                        .color(Color::RED),
+                    );
+                }
+            } else if out_of_bounds && !state.is_added() {
+                client.send_chat_message(
+                    "Your score was ".italic()
+                        + state
+                            .score
+                            .to_string()
+                            .color(Color::GOLD)
+                            .bold()
+                            .not_italic(),
+                );
+
+                if state.score > 0 {
+                    count_run(&username.0, &mut score_tracker, &mut objectives, &globals);
+
+                    let failed_jump = state.blocks.get(1).map(|next| next.y - state.blocks[0].y);
+                    score_tracker.run_stats.record(state.score, failed_jump);
+                }
+
+                // Check if this is a new global highscore, blind runs only go on their own board
+                let is_new_highscore = !blind && beats_highscore(&globals.highscore, state.score);
+
+                let is_first_highscore =
+                    is_new_highscore && claim_pioneer(&globals, &mut score_tracker, username);
+
+                if is_new_highscore {
+                    let highscore = HighScore {
+                        username: username.to_string(),
+                        score: state.score,
+                        seed: state.seed,
+                        movements: state.take_recorded_movements(),
+                        course: state.course.clone(),
+                        reached: state.reached,
+                    };
+
+                    globals.highscore = Some(highscore);
+                }
+
+                if state.score > 0 {
+                    // Save the run count along with any new highscore
+                    let saved = match save_game_data(&globals.highscore, &score_tracker) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            eprintln!("Failed to save game data: {}", e);
+                            false
+                        }
+                    };
+
+                    if is_first_highscore {
+                        client.set_title_times(10, 60, 20);
+                        client.set_subtitle("You earned the Pioneer badge".color(Color::AQUA));
+                        client.set_title("FIRST CHAMPION!".color(Color::GOLD).bold());
+                        client.send_chat_message(
+                            "You set the very first record on this server! Everyone after you \
+                             is chasing your run."
+                                .color(Color::AQUA),
+                        );
+                    }
+
+                    if is_new_highscore && saved {
+                        client.send_chat_message(
+                            "NEW GLOBAL HIGHSCORE! ".color(Color::GOLD).bold()
+                                + format!("Score: {} - Your run has been saved!", state.score)
+                                    .color(Color::GREEN),
+                        );
+                    } else if is_new_highscore {
+                        client.send_chat_message(
+                            "NEW GLOBAL HIGHSCORE! ".color(Color::GOLD).bold()
+                                + format!("Score: {} - ", state.score).color(Color::GREEN)
+                                + "but your run could not be saved and may be lost on restart."
+                                    .color(Color::RED),
+                        );
+                    }
+                }
+            }
+
+            // Despawn the NPC belonging to this player when they fall
+            if let Some(replay) = replay_mode {
+                if let Some(npc_entity) = replay.spawned_npc {
+                    despawn_ghost(&mut commands, npc_entity);
+                }
+            }
+            if let Some(ghost) = self_ghost
+                .as_mut()
+                .and_then(|self_ghost| self_ghost.ghost.take())
+            {
+                despawn_ghost(&mut commands, ghost);
+            }
+
+            // Remove ReplayMode component if it exists
+            commands
+                .entity(player_entity)
+                .remove::<ReplayMode>()
+                .remove::<FinishedRace>()
+                .remove::<Sprint>();
+
+            for pos in ChunkView::new(START_POS.into(), VIEW_DIST).iter() {
+                layer.insert_chunk(pos, UnloadedChunk::new());
+            }
+
+            state.score = 0;
+            state.combo = 0;
+            state.last_milestone = 0;
+            state.seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            state.movements.clear();
+            state.held_frame = None;
+            state.movement_start_time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis();
+            state.rng = StdRng::seed_from_u64(state.seed);
+            state.recording_started = false;
+            state.suspicious_movement = false;
+            state.block = if config.block_themes {
+                *BLOCK_THEMES.choose(&mut rand::rng()).unwrap()
+            } else {
+                BlockState::OBSIDIAN
+            };
+
+            // Players racing themselves get their last run's course again, with its ghost on it,
+            // unless hardcore was switched on by a reload since
+            let last_run = self_ghost
+                .as_ref()
+                .filter(|_| !config.hardcore)
+                .and_then(|_| last_runs.0.get(&uuid.0.as_u128()));
+            let planned = match last_run {
+                Some(last_run) => {
+                    state.seed = last_run.seed;
+                    state.rng = StdRng::seed_from_u64(last_run.seed);
+                    last_run.course.iter().skip(1).copied().collect()
+                }
+                None => VecDeque::new(),
+            };
+            restart_course(
+                &mut state,
+                &mut layer,
+                &profile,
+                &config,
+                &special_blocks,
+                planned,
+                blind,
+            );
+
+            if let (Some(self_ghost), Some(last_run)) = (&mut self_ghost, last_run) {
+                if !last_run.movements.is_empty() {
+                    let style = state.ghost_style.unwrap_or(config.ghost_style);
+                    self_ghost.ghost = Some(spawn_champion_ghost(
+                        &mut commands,
+                        &uuids,
+                        player_entity,
+                        last_run,
+                        style,
+                        false,
+                    ));
+                    client.send_chat_message(
+                        format!(
+                            "Your last run ({}) is racing you on its course.",
+                            last_run.score
+                        )
+                        .color(Color::AQUA),
+                    );
+                }
+            }
+
+            pos.set([
+                f64::from(START_POS.x) + 0.5,
+                f64::from(START_POS.y) + 1.0,
+                f64::from(START_POS.z) + 0.5,
+            ]);
+            look.yaw = 0.0;
+            look.pitch = 0.0;
+        }
+    }
+}
+
+fn manage_blocks(
+    mut clients: Query<
+        (
+            Entity,
+            &mut Client,
+            &mut Position,
+            &mut GameState,
+            &mut ChunkLayer,
+            &Username,
+            Option<&ReplayMode>,
+            Option<&mut Sprint>,
+            Has<Blind>,
+            Option<&mut SelfGhost>,
+        ),
+        Without<Watching>,
+    >,
+    mut objectives: Query<&mut ObjectiveScores, With<Objective>>,
+    globals: Res<Globals>,
+    mut score_tracker: ResMut<ScoreTracker>,
+    profile: Res<JumpProfile>,
+    config: Res<Config>,
+    special_blocks: Res<SpecialBlocks>,
+    paused: Res<Paused>,
+    uuids: Query<&UniqueId>,
+    mut commands: Commands,
+) {
+    if paused.started_at.is_some() {
+        return;
+    }
+
+    for (
+        entity,
+        mut client,
+        mut pos,
+        mut state,
+        mut layer,
+        username,
+        existing_replay_mode,
+        mut sprint,
+        blind,
+        mut self_ghost,
+    ) in &mut clients
+    {
+        let pos_under_player = block_under_player(&pos);
+
+        match special_blocks.action_at(pos_under_player, &layer) {
+            Some(SpecialBlockAction::RaceChampion) if config.hardcore => {
+                if !state.race_prompted {
+                    state.race_prompted = true;
+                    client.send_chat_message(
+                        "Racing the champion is disabled in hardcore mode.".color(Color::RED),
+                    );
+                }
+            }
+            // Race the champion's ghost on their course
+            Some(SpecialBlockAction::RaceChampion)
+                if globals
+                    .highscore
+                    .as_ref()
+                    .is_some_and(|highscore| highscore.score < config.min_race_score) =>
+            {
+                client.send_chat_message(
+                    format!(
+                        "Champion score too low to race yet, come back once it's {} or more.",
+                        config.min_race_score
+                    )
+                    .color(Color::RED),
+                );
+
+                // Step them off the gold block so the message isn't repeated every tick
+                let block = state.blocks[0];
+                pos.set([
+                    f64::from(block.x) + 0.5,
+                    f64::from(block.y) + 1.0,
+                    f64::from(block.z) + 0.5,
+                ]);
+            }
+            Some(SpecialBlockAction::RaceChampion)
+                if config.race_confirm && !state.race_confirmed && globals.highscore.is_some() =>
+            {
+                if !state.race_prompted {
+                    state.race_prompted = true;
+                    client.send_chat_message(
+                        "The gold block starts a race against the champion's ghost on their \
+                         course. Type /race to confirm."
+                            .color(Color::YELLOW),
+                    );
+                }
+            }
+            Some(SpecialBlockAction::RaceChampion) => {
+                // Check if there's a global highscore
+                if let Some(highscore) = &globals.highscore {
+                    // Remove any existing NPC for this player, their own ghost would be on
+                    // another course
+                    if let Some(replay_mode) = existing_replay_mode {
+                        if let Some(existing_npc) = replay_mode.spawned_npc {
+                            despawn_ghost(&mut commands, existing_npc);
+                        }
+                    }
+                    if let Some(ghost) = self_ghost
+                        .as_mut()
+                        .and_then(|self_ghost| self_ghost.ghost.take())
+                    {
+                        despawn_ghost(&mut commands, ghost);
+                    }
+
+                    // Store original seed and switch to highscore seed
+                    state.seed = highscore.seed;
+                    state.rng = StdRng::seed_from_u64(highscore.seed);
+                    state.score = 0;
+                    state.last_milestone = 0;
+                    // Don't clear movements here - we need them for potential highscore
+                    state.recording_started = false;
+
+                    // Clear and regenerate the parkour as the highscore run's course
+                    let course = champion_course(highscore, &profile);
+                    restart_course(
+                        &mut state,
+                        &mut layer,
+                        &profile,
+                        &config,
+                        &special_blocks,
+                        course.into_iter().skip(1).collect(),
+                        blind,
+                    );
+
+                    // A ghost without frames would only be despawned again right away, so
+                    // runs saved without them are raced on score alone
+                    let npc_entity = if highscore.movements.is_empty() {
+                        client.send_chat_message(
+                            "The champion's run has no replay data, so there's no ghost. Race \
+                             their score instead!"
+                                .color(Color::YELLOW),
+                        );
+                        None
+                    } else {
+                        let style = state.ghost_style.unwrap_or(config.ghost_style);
+                        Some(spawn_champion_ghost(
+                            &mut commands,
+                            &uuids,
+                            entity,
+                            highscore,
+                            style,
+                            false,
+                        ))
+                    };
+
+                    // Add replay mode component to the player with reference to the spawned NPC,
+                    // a race takes over from a sprint
+                    commands
+                        .entity(entity)
+                        .remove::<Sprint>()
+                        .insert(ReplayMode {
+                            spawned_npc: npc_entity,
+                            finish_index: highscore.reached,
+                        });
+
+                    client.play_sound(
+                        Sound::EntityPlayerLevelup,
+                        config.sound_category,
+                        pos.0,
+                        1.0,
+                        1.0,
+                    );
+
+                    client.send_chat_message(
+                        format!(
+                            "Loading {}'s highscore run (Score: {})!",
+                            highscore.username, highscore.score
+                        )
+                        .color(Color::GOLD),
+                    );
+
+                    // Teleport player back to spawn
+                    pos.set([
+                        START_POS.x as f64 + 0.5,
+                        START_POS.y as f64 + 1.0,
+                        START_POS.z as f64 + 0.5,
+                    ]);
+                } else {
+                    client.send_chat_message(
+                        "Nobody has set a record yet, so there's no one to race!"
+                            .color(Color::YELLOW),
+                    );
+                    client.send_chat_message(
+                        "Finish a run to become the very first champion and earn the Pioneer \
+                         badge."
+                            .color(Color::AQUA),
+                    );
+                }
+            }
+            // Needs a layer of its own, see `start_watching_champion`
+            Some(SpecialBlockAction::WatchChampion) | None => {}
+        }
+
+        // Regular parkour logic
+        if let Some(index) = course_block_under_player(&state, &pos) {
+            if index > 0 {
+                let current_time_millis = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis();
+
+                // Only award landings the player could have actually jumped to
+                let previous_block = state.blocks[0];
+                let airtime = current_time_millis.saturating_sub(state.last_block_timestamp);
+                let graced = config.first_jump_grace && previous_block == START_POS && index == 1;
+                if graced {
+                    state.suspicious_movement = false;
+                } else if state.suspicious_movement
+                    || !is_plausible_landing(previous_block, state.blocks[index], airtime)
+                {
+                    state.suspicious_movement = false;
+                    println!(
+                        "Rejected implausible landing by {} on block {} after {}ms",
+                        username, index, airtime
+                    );
+                    client.send_chat_message(
+                        "That landing didn't look legitimate and was not counted."
+                            .color(Color::RED),
+                    );
+                    respawn_on_last_block(&mut state, &mut pos, &config);
+                    continue;
+                }
+
+                // Waiting around on the start block says nothing about skill
+                if previous_block != START_POS {
+                    state.record_jump_time(airtime / index as u128);
+                }
+
+                state.start_recording(
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_millis(),
+                );
+                let previous_combo = state.combo;
+                let (combo, bonus_points) =
+                    score_landing(state.combo, index as u32, airtime, &config);
+                state.combo = combo;
+
+                // The blocks left behind are about to disappear
+                let cleared_blocks: Vec<(BlockPos, BlockState)> = if config.block_break_effects {
+                    state
+                        .blocks
+                        .iter()
+                        .take(index)
+                        .map(|block| (*block, layer.block(*block).unwrap_or_default().state))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+
+                generate_next_blocks(&mut state, &mut layer, &profile, &config, index, true);
+                score_tracker.total_jumps += index as u64;
+
+                for (block, block_state) in cleared_blocks {
+                    let center = [
+                        f64::from(block.x) + 0.5,
+                        f64::from(block.y) + 0.5,
+                        f64::from(block.z) + 0.5,
+                    ];
+                    client.play_particle(
+                        &Particle::Block(block_state),
+                        false,
+                        center,
+                        [0.25, 0.25, 0.25],
+                        0.0,
+                        20,
+                    );
+                    client.play_sound(
+                        Sound::BlockStoneBreak,
+                        config.sound_category,
+                        center,
+                        0.5,
+                        1.0,
+                    );
+                }
+
+                state.score = state.score.saturating_add(bonus_points);
+
+                if let (Some(replay_mode), Some(highscore)) =
+                    (existing_replay_mode, &globals.highscore)
+                {
+                    if config.race_finish && state.reached >= replay_mode.finish_index {
+                        finish_race(&mut client, &state, highscore, replay_mode);
+                        commands.entity(entity).insert(FinishedRace);
+                    }
+                }
+
+                let pitch = 0.9 + ((state.combo as f32) - 1.0) * 0.05;
+                client.play_sound(
+                    Sound::BlockNoteBlockBass,
+                    config.sound_category,
+                    pos.0,
+                    1.0,
+                    pitch,
+                );
+
+                client.set_action_bar(score_action_bar(&state, &profile));
+
+                // Titles sent later on this landing, for a milestone or a finish, replace this
+                if let Some(callout) =
+                    combo_callout(previous_combo, state.combo, &config.combo_callouts)
+                {
+                    client.set_title_times(0, 20, 10);
+                    client.set_subtitle(
+                        format!("x{} COMBO!", callout)
+                            .color(combo_color(state.combo))
+                            .bold(),
+                    );
+                    client.set_title("");
+                }
+
+                if let Some(sprint) = sprint.as_deref_mut() {
+                    if !sprint.finished && state.reached >= sprint.blocks as usize {
+                        finish_sprint(
+                            &mut client,
+                            &state,
+                            sprint,
+                            username,
+                            &mut score_tracker,
+                            &globals,
+                        );
+                    }
+
+                    // Sprint scores don't count towards milestones or the boards
+                    continue;
+                }
+
+                // Celebrate the highest milestone just passed, a big combo can skip past several
+                if let Some(&milestone) = config
+                    .milestones
+                    .iter()
+                    .rev()
+                    .find(|&&milestone| milestone <= state.score)
+                {
+                    if milestone > state.last_milestone {
+                        state.last_milestone = milestone;
+                        client.set_title_times(5, 30, 10);
+                        client.set_subtitle("Keep it going!".color(Color::GRAY));
+                        client.set_title(milestone.to_string().color(Color::GOLD).bold());
+                        client.play_particle(
+                            &Particle::Firework,
+                            false,
+                            pos.0,
+                            [0.5, 1.0, 0.5],
+                            0.1,
+                            40,
+                        );
+                        client.play_sound(
+                            Sound::UiToastChallengeComplete,
+                            config.sound_category,
+                            pos.0,
+                            1.0,
+                            1.0,
+                        );
+                    }
+                }
+                let name = username.to_string();
+                let new_score = state.score as i32;
+
+                // Blind runs only go on their own board, saved when the run ends
+                if blind {
+                    if new_score > score_tracker.blind_scores.get(&name).copied().unwrap_or(0) {
+                        score_tracker.set(Board::Blind, &name, new_score);
+                        show_top_15(Board::Blind, &score_tracker, &mut objectives, &globals);
+                    }
+                    continue;
+                }
+
+                // Best combos are persisted with the next save when the run ends
+                let combo = state.combo as i32;
+                if combo > score_tracker.best_combos.get(&name).copied().unwrap_or(0) {
+                    score_tracker.set(Board::Combo, &name, combo);
+                    show_top_15(Board::Combo, &score_tracker, &mut objectives, &globals);
+                }
+
+                // Update score tracker
+                let old_score = score_tracker.scores.get(&name).copied().unwrap_or(0);
+                if new_score > old_score {
+                    score_tracker.set(Board::Score, &name, new_score);
+                    show_top_15(Board::Score, &score_tracker, &mut objectives, &globals);
+
+                    // Check if top 15 changed, it's saved on the next autosave
+                    if score_tracker.top_15(Board::Score) != score_tracker.last_saved_top_15 {
+                        score_tracker.top_15_unsaved = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Removes course blocks a player got far past without landing on, and generates as many new ones
+/// ahead so the course keeps its length. They don't count towards the score.
+fn clean_up_passed_blocks(
+    mut clients: Query<(&Position, &mut GameState, &mut ChunkLayer), Without<Watching>>,
+    profile: Res<JumpProfile>,
+    config: Res<Config>,
+    paused: Res<Paused>,
+) {
+    let Some(distance) = config.passed_block_cleanup_distance else {
+        return;
+    };
+    // Courses are frozen along with everything else
+    if paused.started_at.is_some() {
+        return;
+    }
+
+    for (pos, mut state, mut layer) in &mut clients {
+        let behind_z = pos.0.z.floor() as i32 - distance;
+        let mut removed = 0;
+        while state.blocks.len() > 1 && state.blocks[0].z < behind_z {
+            let block = state.blocks.pop_front().unwrap();
+            layer.set_block(block, BlockState::AIR);
+            removed += 1;
+        }
+
+        if removed > 0 {
+            // Nothing was landed on, so the combo window, airtime and stuck timer keep running
+            let last_block_timestamp = state.last_block_timestamp;
+            generate_next_blocks(&mut state, &mut layer, &profile, &config, removed, false);
+            state.last_block_timestamp = last_block_timestamp;
+        }
+    }
+}
+
+/// Broadcasts the next of `Config::tips` every `tip_interval_ms` while anyone is online.
+fn broadcast_tips(
+    mut clients: Query<&mut Client>,
+    globals: Res<Globals>,
+    config: Res<Config>,
+    mut last_tip: Local<u128>,
+    mut next_tip: Local<usize>,
+) {
+    let Some(interval) = config.tip_interval_ms else {
+        return;
+    };
+
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    if *last_tip == 0 {
+        // Players get the join messages first, the first tip comes an interval after startup
+        *last_tip = current_time;
+    }
+    if current_time.saturating_sub(*last_tip) < interval || clients.is_empty() {
+        return;
+    }
+    *last_tip = current_time;
+
+    let champion = globals.highscore.as_ref();
+    let tip = (0..config.tips.len())
+        .map(|offset| (*next_tip + offset) % config.tips.len())
+        .find(|&index| {
+            let tip = &config.tips[index];
+            champion.is_some() || !(tip.contains("{champion}") || tip.contains("{score}"))
+        });
+    let Some(index) = tip else {
+        return;
+    };
+    *next_tip = index + 1;
+
+    let mut tip = config.tips[index].clone();
+    if let Some(champion) = champion {
+        tip = tip
+            .replace("{champion}", &champion.username)
+            .replace("{score}", &champion.score.to_string());
+    }
+    for mut client in &mut clients {
+        client.send_chat_message("[Tip] ".color(Color::AQUA) + tip.clone().color(Color::GRAY));
+    }
+}
+
+/// The score shown in the action bar after every landing.
+fn score_action_bar(state: &GameState, profile: &JumpProfile) -> Text {
+    let mut action_bar = state
+        .score
+        .to_string()
+        .color(combo_color(state.combo))
+        .bold();
+    if let Some(remaining) = profile.blocks_until_next_tier(state.score) {
+        if remaining <= TIER_COUNTDOWN_BLOCKS {
+            action_bar = action_bar
+                + format!("  Next tier in {} blocks", remaining)
+                    .color(Color::GRAY)
+                    .not_bold();
+        }
+    }
+    action_bar
+}
+
+/// Sends a player's score to the action bar again every `action_bar_refresh_ms` it stays the
+/// same, as `manage_blocks` only sends it on landings and action bars fade after a few seconds.
+/// Races are left to `show_race_gap`, which refreshes its own.
+fn keep_score_visible(
+    mut players: Query<(Entity, &mut Client, &GameState, Option<&ReplayMode>)>,
+    profile: Res<JumpProfile>,
+    config: Res<Config>,
+    mut shown: Local<HashMap<Entity, (u32, u128)>>,
+) {
+    let Some(interval) = config.action_bar_refresh_ms else {
+        return;
+    };
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    shown.retain(|entity, _| players.contains(*entity));
+
+    for (entity, mut client, state, replay_mode) in &mut players {
+        if state.score == 0 || replay_mode.is_some_and(|replay| replay.spawned_npc.is_some()) {
+            shown.remove(&entity);
+            continue;
+        }
+
+        // A new score was just sent by `manage_blocks`, count from there
+        match shown.get(&entity) {
+            Some(&(score, sent_at)) if score == state.score => {
+                if current_time.saturating_sub(sent_at) < interval {
+                    continue;
+                }
+            }
+            _ => {
+                shown.insert(entity, (state.score, current_time));
+                continue;
+            }
+        }
+        shown.insert(entity, (state.score, current_time));
+        client.set_action_bar(score_action_bar(state, &profile));
+    }
+}
+
+/// Shows racing players how many blocks they're ahead of or behind the champion's ghost, next to
+/// their score. It's sent again whenever either of them reaches a new block, and every
+/// `RACE_GAP_REFRESH_MS` so it doesn't fade.
+fn show_race_gap(
+    mut players: Query<(Entity, &mut Client, &GameState, &ReplayMode)>,
+    ghosts: Query<(&Position, &ReplayNpc)>,
+    profile: Res<JumpProfile>,
+    mut shown: Local<HashMap<Entity, (usize, usize, u128)>>,
+) {
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    shown.retain(|entity, _| players.contains(*entity));
+
+    for (entity, mut client, state, replay_mode) in &mut players {
+        let Some((ghost_pos, ghost)) = replay_mode.spawned_npc.and_then(|npc| ghosts.get(npc).ok())
+        else {
+            continue;
+        };
+        if !ghost.replay_started {
+            continue;
+        }
+
+        // Courses only ever go forward, so the ghost is past every block behind it
+        let reached = state.reached;
+        let ghost_z = ghost_pos.0.z.floor() as i32;
+        let ghost_reached = state
+            .course
+            .partition_point(|block| block.z <= ghost_z)
+            .saturating_sub(1);
+
+        if let Some(&(last_reached, last_ghost_reached, sent_at)) = shown.get(&entity) {
+            if last_reached == reached
+                && last_ghost_reached == ghost_reached
+                && current_time.saturating_sub(sent_at) < RACE_GAP_REFRESH_MS
+            {
+                continue;
+            }
+        }
+        shown.insert(entity, (reached, ghost_reached, current_time));
+
+        let gap = if reached > ghost_reached {
+            format!("+{} ahead", reached - ghost_reached).color(Color::GREEN)
+        } else if reached < ghost_reached {
+            format!("-{} behind", ghost_reached - reached).color(Color::RED)
+        } else {
+            "level".color(Color::GRAY)
+        };
+        client.set_action_bar(score_action_bar(state, &profile) + "  " + gap.not_bold());
+    }
+}
+
+/// Writes the best run beating the champion to `PENDING_RUN_FILE` every
+/// `run_snapshot_interval_ms` while it lasts, for `setup` to recover after a crash. Once no run is
+/// beating the champion, which is right after that run ended and was saved, the file is removed.
+fn snapshot_pending_runs(
+    players: Query<(&GameState, &Username), (Without<Blind>, Without<Sprint>)>,
+    globals: Res<Globals>,
+    config: Res<Config>,
+    mut snapshot_at: Local<Option<u128>>,
+) {
+    let Some(interval) = config.run_snapshot_interval_ms else {
+        return;
+    };
+
+    let champion_score = globals
+        .highscore
+        .as_ref()
+        .map_or(0, |highscore| highscore.score);
+    let path = data_path(PENDING_RUN_FILE);
+    let Some((state, username)) = players
+        .iter()
+        .filter(|(state, _)| state.score > champion_score)
+        .max_by_key(|(state, _)| state.score)
+    else {
+        if snapshot_at.take().is_some() {
+            if let Err(e) = fs::remove_file(&path) {
+                eprintln!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+        return;
+    };
+
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    if snapshot_at.is_some_and(|at| current_time.saturating_sub(at) < interval) {
+        return;
+    }
+
+    let run = Some(HighScore {
+        username: username.to_string(),
+        score: state.score,
+        seed: state.seed,
+        movements: state.recorded_movements(),
+        course: state.course.clone(),
+        reached: state.reached,
+    });
+    // Failed snapshots wait for the next interval too, instead of being retried every tick
+    if let Err(e) = write_save_data(&path, &highscore_save_data(&run)) {
+        eprintln!("Failed to snapshot the run of {}: {}", username, e);
+    }
+    *snapshot_at = Some(current_time);
+}
+
+/// Writes top 15 changes from `manage_blocks` to disk every `scoreboard_save_interval_ms`, so a
+/// hot run doesn't save on every block.
+fn autosave_scoreboard(
+    mut score_tracker: ResMut<ScoreTracker>,
+    globals: Res<Globals>,
+    config: Res<Config>,
+    mut last_save: Local<u128>,
+) {
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    if !score_tracker.top_15_unsaved
+        || current_time.saturating_sub(*last_save) < config.scoreboard_save_interval_ms
+    {
+        return;
+    }
+    *last_save = current_time;
+
+    if let Err(e) = save_game_data(&globals.highscore, &score_tracker) {
+        eprintln!("Failed to save game data: {}", e);
+        return;
+    }
+    score_tracker.last_saved_top_15 = score_tracker.top_15(Board::Score);
+    score_tracker.top_15_unsaved = false;
+}
+
+fn start_watching_champion(
+    mut clients: Query<
+        (
+            Entity,
+            &mut Client,
+            &mut Position,
+            &mut GameMode,
+            &mut VisibleChunkLayer,
+            &ChunkLayer,
+            &GameState,
+            Option<&ReplayMode>,
+        ),
+        Without<Watching>,
+    >,
+    globals: Res<Globals>,
+    profile: Res<JumpProfile>,
+    config: Res<Config>,
+    special_blocks: Res<SpecialBlocks>,
+    paused: Res<Paused>,
+    server: Res<Server>,
+    dimensions: Res<DimensionTypeRegistry>,
+    biomes: Res<BiomeRegistry>,
+    uuids: Query<&UniqueId>,
+    mut commands: Commands,
+) {
+    if paused.started_at.is_some() {
+        return;
+    }
+
+    for (
+        entity,
+        mut client,
+        mut pos,
+        mut game_mode,
+        mut visible_chunk_layer,
+        layer,
+        state,
+        replay_mode,
+    ) in &mut clients
+    {
+        let pos_under_player = block_under_player(&pos);
+        if special_blocks.action_at(pos_under_player, layer)
+            != Some(SpecialBlockAction::WatchChampion)
+        {
+            continue;
+        }
+
+        let highscore = match &globals.highscore {
+            Some(highscore) if !highscore.movements.is_empty() => highscore,
+            _ => {
+                client
+                    .send_chat_message("There is no champion run to watch yet!".color(Color::RED));
+
+                // Step them off the diamond block so the message isn't repeated every tick
+                let block = state.blocks[0];
+                pos.set([
+                    f64::from(block.x) + 0.5,
+                    f64::from(block.y) + 1.0,
+                    f64::from(block.z) + 0.5,
+                ]);
+                continue;
+            }
+        };
+
+        // Watching replaces a race that's waiting to start
+        if let Some(npc_entity) = replay_mode.and_then(|replay| replay.spawned_npc) {
+            despawn_ghost(&mut commands, npc_entity);
+        }
+        commands.entity(entity).remove::<ReplayMode>();
+
+        // Build the champion's course on a layer of its own so the player's run is left untouched
+        let mut course_layer = ChunkLayer::new(ident!("the_end"), &dimensions, &biomes, &server);
+        course_layer.chunk_entry(START_POS.into()).or_default();
+        course_layer.set_block(START_POS, BlockState::BLACK_WOOL);
+        for block_pos in champion_course(highscore, &profile).into_iter().skip(1) {
+            course_layer.chunk_entry(block_pos.into()).or_default();
+            course_layer.set_block(block_pos, *BLOCK_TYPES.choose(&mut rand::rng()).unwrap());
+        }
+        let course_layer = commands.spawn(course_layer).id();
+
+        let style = state.ghost_style.unwrap_or(config.ghost_style);
+        let ghost = spawn_champion_ghost(&mut commands, &uuids, entity, highscore, style, true);
+
+        visible_chunk_layer.0 = course_layer;
+        *game_mode = GameMode::Spectator;
+        commands.entity(entity).insert(Watching {
+            ghost,
+            course_layer,
+        });
+
+        client.send_chat_message(
+            format!(
+                "Watching {}'s record run (Score: {})...",
+                highscore.username, highscore.score
+            )
+            .color(Color::AQUA),
+        );
+        client.send_chat_message(
+            "Fly along with the ghost, you'll be sent back when it's over."
+                .italic()
+                .color(Color::GRAY),
+        );
+    }
+}
+
+fn finish_watching_champion(
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        &mut Position,
+        &mut GameMode,
+        &mut VisibleChunkLayer,
+        &mut GameState,
+        &Watching,
+    )>,
+    ghosts: Query<(), (With<ReplayNpc>, Without<Despawned>)>,
+    mut commands: Commands,
+) {
+    for (
+        entity,
+        mut client,
+        mut pos,
+        mut game_mode,
+        mut visible_chunk_layer,
+        mut state,
+        watching,
+    ) in &mut clients
+    {
+        if ghosts.contains(watching.ghost) {
+            continue;
+        }
+
+        visible_chunk_layer.0 = entity;
+        *game_mode = GameMode::Adventure;
+        commands.entity(watching.course_layer).insert(Despawned);
+        commands.entity(entity).remove::<Watching>();
+
+        // Put them back on their own course, clear of the diamond block
+        let block = state.blocks[0];
+        pos.set([
+            f64::from(block.x) + 0.5,
+            f64::from(block.y) + 1.0,
+            f64::from(block.z) + 0.5,
+        ]);
+        state.last_block_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        client.send_chat_message("That's the record to beat! Back to your run.".color(Color::AQUA));
+    }
+}
+
+/// Each player's course lives on their own chunk layer (or the champion course they're watching).
+/// A shared layer would overlap courses, so log loudly if that invariant is ever broken.
+fn check_chunk_layer_ownership(
+    changed: Query<(), Changed<VisibleChunkLayer>>,
+    clients: Query<(Entity, &Username, &VisibleChunkLayer, Option<&Watching>), With<Client>>,
+) {
+    if changed.is_empty() {
+        return;
+    }
+
+    let mut viewers: HashMap<Entity, &Username> = HashMap::new();
+    for (entity, username, visible_chunk_layer, watching) in &clients {
+        let expected_layer = watching.map_or(entity, |watching| watching.course_layer);
+        if visible_chunk_layer.0 != expected_layer {
+            eprintln!(
+                "{} is viewing chunk layer {:?} instead of their own {:?}",
+                username, visible_chunk_layer.0, expected_layer
+            );
+        }
+
+        if let Some(other) = viewers.insert(visible_chunk_layer.0, username) {
+            eprintln!(
+                "{} and {} share chunk layer {:?}, their courses will overlap",
+                other, username, visible_chunk_layer.0
+            );
+        }
+    }
+}
+
+/// Spawns a ghost replaying `highscore` on the owner's entity layer. Unless `start_immediately`
+/// is set, the replay waits for the owner to score their first block.
+fn spawn_champion_ghost(
+    commands: &mut Commands,
+    uuids: &Query<&UniqueId>,
+    owner: Entity,
+    highscore: &HighScore,
+    style: GhostStyle,
+    start_immediately: bool,
+) -> Entity {
+    // Get the first recorded position from the highscore movements
+    let (npc_pos, npc_yaw, npc_pitch) = if let Some(first_movement) = highscore.movements.first() {
+        (
+            Position::new(first_movement.position),
+            first_movement.yaw,
+            first_movement.pitch,
+        )
+    } else {
+        // Fallback to spawn position if no movements recorded
+        (
+            Position::new([
+                START_POS.x as f64 + 0.5,
+                START_POS.y as f64 + 1.0,
+                START_POS.z as f64 + 0.5,
+            ]),
+            0.0,
+            0.0,
+        )
+    };
+
+    // Random UUIDs practically never collide, but a ghost sharing one with a player, another
+    // ghost or a tab list entry would mix up their skins and entries
+    let mut npc_id = UniqueId::default();
+    while uuids.iter().any(|uuid| uuid.0 == npc_id.0) {
+        npc_id = UniqueId::default();
+    }
+
+    // Spawn the player entity with replay component
+    let entity_bundle = PlayerEntityBundle {
+        layer: EntityLayerId(owner),
+        uuid: npc_id,
+        position: npc_pos,
+        look: Look::new(npc_yaw, npc_pitch),
+        head_yaw: HeadYaw(npc_yaw),
+        entity_flags: style.flags(),
+        ..Default::default()
+    };
+
+    let spawn_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let replay_component = ReplayNpc {
+        movements: highscore.movements.clone(),
+        current_index: 0,
+        start_time: spawn_time,
+        replay_started: start_immediately,
+        owner_entity: owner,
+        spawned_at: spawn_time,
+        distance_since_step_sound: 0.0,
+    };
+
+    let npc_entity = commands
+        .spawn((
+            entity_bundle,
+            replay_component,
+            GameMode::Spectator,
+            // No `NoCollisionTeam`: only the owner sees the ghost, and `add_ghosts_to_team`
+            // puts it in their ghost team without telling every other client
+        ))
+        .id();
+
+    // Add player list entry so the player is visible. It's never listed, and the space in the
+    // name keeps it from ever matching a real username
+    commands.spawn((
+        PlayerListEntryBundle {
+            uuid: npc_id,
+            username: Username(ghost_name(&highscore.username)),
+            display_name: DisplayName(
+                format!("{}'s Ghost ({})", highscore.username, highscore.score)
+                    .color(Color::GOLD)
+                    .into(),
+            ),
+            listed: Listed(false), // Don't show in player list
+            ..Default::default()
+        },
+        GhostPlayerListEntry {
+            ghost_entity: npc_entity,
+        },
+    ));
+
+    npc_entity
+}
+
+/// Name of `username`'s ghost, shortened to fit the 16 character limit on names. Counts
+/// characters rather than bytes, so names with multibyte characters are cut between them.
+fn ghost_name(username: &str) -> String {
+    if username.chars().count() > 10 {
+        format!("{}. Ghost", username.chars().take(7).collect::<String>())
+    } else {
+        format!("{} Ghost", username)
+    }
+}
+
+/// Marks a ghost for despawning. Ghosts can end on their own, so it may already be gone.
+fn despawn_ghost(commands: &mut Commands, ghost: Entity) {
+    if let Some(mut ghost) = commands.get_entity(ghost) {
+        ghost.try_insert(Despawned);
+    }
+}
+
+/// Flags clients whose position jumped further in one tick than movement allows. Runs before
+/// anything in the tick teleports players, so only client-reported movement is measured.
+fn detect_teleports(
+    mut clients: Query<
+        (&Position, &OldPosition, &mut GameState),
+        (With<Client>, Without<Watching>),
+    >,
+) {
+    for (pos, old_pos, mut state) in &mut clients {
+        let delta = pos.0 - old_pos.get();
+        if delta.x.hypot(delta.z) > MAX_TICK_MOVEMENT {
+            state.suspicious_movement = true;
+        }
+    }
+}
+
+/// Moves a player's course and the player back towards the start in whole chunks once they're
+/// `Config::recenter_distance` blocks away from it. This only happens while they stand on their
+/// block, where the teleport can't be felt, and not during races, with a pacer or while racing
+/// their own last run, whose ghosts follow the course where it was.
+fn recenter_long_runs(
+    mut clients: Query<
+        (
+            &mut Position,
+            &mut GameState,
+            &mut ChunkLayer,
+            Option<&SelfGhost>,
+        ),
+        (
+            With<Client>,
+            Without<Watching>,
+            Without<ReplayMode>,
+            Without<Pacer>,
+        ),
+    >,
+    config: Res<Config>,
+    paused: Res<Paused>,
+) {
+    let Some(distance) = config.recenter_distance else {
+        return;
+    };
+    if paused.started_at.is_some() {
+        return;
+    }
+
+    for (mut pos, mut state, mut layer, self_ghost) in &mut clients {
+        if self_ghost.is_some_and(|self_ghost| self_ghost.ghost.is_some()) {
+            continue;
+        }
+
+        let from_start = (pos.0.x - f64::from(START_POS.x)).hypot(pos.0.z - f64::from(START_POS.z));
+        if from_start < f64::from(distance) || block_under_player(&pos) != state.blocks[0] {
+            continue;
+        }
+
+        let current = state.blocks[0];
+        let shift_x = (current.x - START_POS.x).div_euclid(16) * 16;
+        let shift_z = (current.z - START_POS.z - RECENTER_MARGIN).div_euclid(16) * 16;
+        let shift = |block: BlockPos| BlockPos::new(block.x - shift_x, block.y, block.z - shift_z);
+
+        let blocks: Vec<(BlockPos, BlockState)> = state
+            .blocks
+            .iter()
+            .map(|&block| (block, layer.block(block).unwrap_or_default().state))
+            .collect();
+        for (block, _) in &blocks {
+            layer.set_block(*block, BlockState::AIR);
+        }
+
+        let old_view = ChunkView::new(pos.0.into(), VIEW_DIST);
+        pos.set([
+            pos.0.x - f64::from(shift_x),
+            pos.0.y,
+            pos.0.z - f64::from(shift_z),
+        ]);
+        let view = ChunkView::new(pos.0.into(), VIEW_DIST);
+        // `manage_chunks` compares with the position before the tick, which is already
+        // updated by the time it runs next
+        for chunk in old_view.diff(view) {
+            layer.remove_chunk(chunk);
+        }
+        for chunk in view.diff(old_view) {
+            layer.chunk_entry(chunk).or_default();
+        }
+
+        for (block, block_state) in blocks {
+            let block = shift(block);
+            layer.chunk_entry(block.into()).or_default();
+            layer.set_block(block, block_state);
+        }
+        state.blocks.move_all(shift);
+        for block in state.planned_blocks.iter_mut() {
+            *block = shift(*block);
+        }
+        state.frame_offset.0 += shift_x;
+        state.frame_offset.1 += shift_z;
+    }
+}
+
+// Chunks of a watching player's own layer are left alone so their course survives while they fly
+// around the champion's
+fn manage_chunks(
+    mut clients: Query<
+        (&Position, &OldPosition, &mut ChunkLayer),
+        (With<Client>, Without<Watching>),
+    >,
+) {
+    for (pos, old_pos, mut layer) in &mut clients {
+        let old_view = ChunkView::new(old_pos.get().into(), VIEW_DIST);
+        let view = ChunkView::new(pos.0.into(), VIEW_DIST);
+
+        if old_view != view {
+            for pos in old_view.diff(view) {
+                layer.remove_chunk(pos);
+            }
+
+            for pos in view.diff(old_view) {
+                layer.chunk_entry(pos).or_default();
+            }
+        }
+    }
+}
+
+/// Scores a landing `blocks` blocks ahead, `elapsed_ms` after the previous one. Returns the new
+/// combo and the bonus points earned on top of the point per block. The combo grows when the
+/// player keeps up with a pace that gets faster the higher it is, and breaks otherwise.
+fn score_landing(combo: u32, blocks: u32, elapsed_ms: u128, config: &Config) -> (u32, u32) {
+    let combo = update_combo(
+        combo,
+        blocks,
+        elapsed_ms,
+        config.combo_window_floor_ms,
+        config.combo_window_ceiling_ms,
+    );
+
+    // Reward keeping a streak going on top of the point per block
+    let bonus_points = ((combo as f64 * config.combo_bonus) as u32).saturating_mul(blocks);
+    (combo, bonus_points)
+}
+
+/// The highest of `callouts` a combo going from `previous` to `combo` reached, if it reached any.
+/// A broken combo reaches none.
+fn combo_callout(previous: u32, combo: u32, callouts: &[u32]) -> Option<u32> {
+    callouts
+        .iter()
+        .rev()
+        .find(|&&callout| previous < callout && callout <= combo)
+        .copied()
+}
+
+/// Color the score is shown in at `combo`, see `COMBO_COLORS`.
+fn combo_color(combo: u32) -> Color {
+    COMBO_COLORS
+        .iter()
+        .rev()
+        .find(|(from, _)| combo >= *from)
+        .map_or(Color::WHITE, |(_, color)| *color)
+}
+
+/// The block a player is standing on, as far as scoring is concerned.
+fn block_under_player(pos: &Position) -> BlockPos {
+    BlockPos::new(
+        (pos.0.x - 0.5).round() as i32,
+        pos.0.y as i32 - 1,
+        (pos.0.z - 0.5).round() as i32,
+    )
+}
+
+/// Index of the furthest course block under the player's hitbox. Checks every block the hitbox
+/// overlaps instead of just the one under its center, so landing on the edge of a block, like
+/// after a diagonal jump, still counts.
+fn course_block_under_player(state: &GameState, pos: &Position) -> Option<usize> {
+    let y = pos.0.y as i32 - 1;
+    let mut furthest = None;
+    for dx in [-LANDING_HALF_WIDTH, LANDING_HALF_WIDTH] {
+        for dz in [-LANDING_HALF_WIDTH, LANDING_HALF_WIDTH] {
+            let block = BlockPos::new(
+                (pos.0.x + dx).floor() as i32,
+                y,
+                (pos.0.z + dz).floor() as i32,
+            );
+            let index = state.blocks.position(block);
+            furthest = furthest.max(index);
+        }
+    }
+    furthest
+}
+
+/// Puts a player back on the last block they reached without ending their run. If they keep
+/// their combo, its timer restarts too, otherwise the time spent respawning would break it on
+/// their very next jump.
+fn respawn_on_last_block(state: &mut GameState, pos: &mut Position, config: &Config) {
+    let block = state.blocks[0];
+    pos.set([
+        f64::from(block.x) + 0.5,
+        f64::from(block.y) + 1.0,
+        f64::from(block.z) + 0.5,
+    ]);
+
+    if config.keep_combo_on_respawn {
+        state.last_block_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+    } else {
+        state.combo = 0;
+    }
+}
+
+/// Adds `count` blocks to the end of the course. In game, the player cleared as many blocks, so
+/// that many are removed from the start and scored. Everything that doesn't depend on the number
+/// of blocks is done once per call, since big combo jumps can add a lot of blocks in one tick.
+fn generate_next_blocks(
+    state: &mut GameState,
+    layer: &mut ChunkLayer,
+    profile: &JumpProfile,
+    config: &Config,
+    count: usize,
+    in_game: bool,
+) {
+    if in_game {
+        for removed_block in state.blocks.drain_front(count) {
+            layer.set_block(removed_block, BlockState::AIR);
+        }
+        // The block landed on is first now
+        state.reached = state.course.len() - state.blocks.len();
+
+        state.score += count as u32;
+    }
+
+    for _ in 0..count {
+        let (block_pos, block_state) = next_block(state, profile);
+        layer.set_block(block_pos, block_state);
+        state.blocks.push_back(block_pos);
+        let course_pos = state.unshifted(block_pos);
+        state.course.push(course_pos);
+    }
+
+    // Blocks only pile up if a reset or race skipped clearing the course, so drop the oldest
+    // ones instead of letting the course grow forever
+    debug_assert!(
+        state.blocks.len() <= config.max_course_blocks,
+        "course grew to {} blocks",
+        state.blocks.len()
+    );
+    if state.blocks.len() > config.max_course_blocks {
+        eprintln!(
+            "Course grew to {} blocks, trimming it to {}",
+            state.blocks.len(),
+            config.max_course_blocks
+        );
+        while state.blocks.len() > config.max_course_blocks {
+            let removed_block = state.blocks.pop_front().unwrap();
+            layer.set_block(removed_block, BlockState::AIR);
+        }
+    }
+
+    state.last_block_timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+}
+
+/// Clears the course and starts it over from the start block with the special blocks next to it,
+/// placing `planned` first and then generating from the run's rng. Resets and races both start
+/// this way, so they keep the same number of blocks ahead.
+fn restart_course(
+    state: &mut GameState,
+    layer: &mut ChunkLayer,
+    profile: &JumpProfile,
+    config: &Config,
+    special_blocks: &SpecialBlocks,
+    planned: VecDeque<BlockPos>,
+    blind: bool,
+) {
+    for block in state.blocks.iter() {
+        layer.set_block(*block, BlockState::AIR);
+    }
+    state.blocks.clear();
+    state.blocks.push_back(START_POS);
+    state.course = vec![START_POS];
+    state.reached = 0;
+    state.planned_blocks = planned;
+    state.frame_offset = (0, 0);
+    layer.set_block(START_POS, BlockState::BLACK_WOOL);
+    special_blocks.place(layer);
+
+    let count = blocks_ahead(config, blind);
+    generate_next_blocks(state, layer, profile, config, count, false);
+}
+
+/// How many course blocks are kept ahead of a player.
+fn blocks_ahead(config: &Config, blind: bool) -> usize {
+    if blind {
+        BLIND_BLOCKS_AHEAD
+    } else {
+        config.course_length - 1
+    }
+}
+
+/// Swaps the course ahead of the player for a new one starting with an easy jump, for when the
+/// next block turned out to be out of reach.
+fn skip_next_block(
+    state: &mut GameState,
+    layer: &mut ChunkLayer,
+    profile: &JumpProfile,
+    config: &Config,
+) {
+    let ahead = state.blocks.len() - 1;
+    for block in state.blocks.split_off(1) {
+        layer.set_block(block, BlockState::AIR);
+    }
+    let course_len = state.course.len() - ahead;
+    state.course.truncate(course_len);
+    state.planned_blocks.clear();
+
+    let easy_block = generate_random_block(
+        state.blocks[0],
+        state.target_y,
+        profile,
+        MIN_ADAPTIVE_HARDNESS,
+        &mut state.rng,
+    );
+    state.planned_blocks.push_back(easy_block);
+    generate_next_blocks(state, layer, profile, config, ahead, false);
+}
+
+/// Picks the block following the end of the course, advancing the course's rng and height
+/// target, or takes the next planned block. The block isn't added to the course.
+fn next_block(state: &mut GameState, profile: &JumpProfile) -> (BlockPos, BlockState) {
+    if let Some(block_pos) = state.planned_blocks.pop_front() {
+        return (block_pos, run_block(state));
+    }
+
+    let last_pos = *state.blocks.back().unwrap();
+    let max_hardness = if profile.adaptive {
+        state.max_jump_hardness()
+    } else {
+        f64::INFINITY
+    };
+    let block_pos = generate_random_block(
+        last_pos,
+        state.target_y,
+        profile,
+        max_hardness,
+        &mut state.rng,
+    );
+
+    if last_pos.y == START_POS.y {
+        state.target_y = 0
+    } else if last_pos.y < START_POS.y - profile.y_band || last_pos.y > START_POS.y + profile.y_band
+    {
+        state.target_y = START_POS.y;
+    }
+
+    (block_pos, run_block(state))
+}
+
+/// The run's block for the next block placed.
+fn run_block(state: &mut GameState) -> BlockState {
+    // Still drawn from the run's rng, so seeds keep generating the courses they did when the
+    // block was picked per block
+    let _ = BLOCK_TYPES.choose(&mut state.rng);
+    state.block
+}
+
+/// The champion's course from the start block on, including the blocks generated ahead of where
+/// the run ended. Runs saved before courses were stored are regenerated from their seed, which
+/// only matches as long as the generator hasn't changed since. Those predate `COURSE_LENGTH`, so
+/// they always had `BLOCKS_AHEAD` blocks ahead.
+fn champion_course(highscore: &HighScore, profile: &JumpProfile) -> Vec<BlockPos> {
+    if !highscore.course.is_empty() {
+        return highscore.course.clone();
+    }
+
+    generate_course(
+        highscore.seed,
+        highscore.score as usize + BLOCKS_AHEAD,
+        profile,
+    )
+}
+
+/// The first `count` blocks `seed` generates after the start block, with the start block first.
+/// Nothing is placed, so this works without a player.
+fn generate_course(seed: u64, count: usize, profile: &JumpProfile) -> Vec<BlockPos> {
+    let mut course = GameState::new(seed);
+    course.blocks.push_back(START_POS);
+    for _ in 0..count {
+        let (block_pos, _) = next_block(&mut course, profile);
+        course.blocks.push_back(block_pos);
+    }
+    course.blocks.iter().copied().collect()
+}
+
+fn record_player_movements(
+    mut clients: Query<
+        (&Position, &Look, &Flags, &mut GameState),
+        (With<Client>, Without<Watching>, Without<Afk>),
+    >,
+    paused: Res<Paused>,
+    config: Res<Config>,
+) {
+    if paused.started_at.is_some() {
+        return;
+    }
+
+    for (pos, look, flags, mut state) in &mut clients {
+        if state.recording_started {
+            let current_time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis();
+
+            let timestamp = current_time.saturating_sub(state.movement_start_time);
+            if timestamp < RECORDING_GRACE_MS {
+                continue;
+            }
+
+            let movement = PlayerMovement {
+                position: [
+                    pos.0.x + f64::from(state.frame_offset.0),
+                    pos.0.y,
+                    pos.0.z + f64::from(state.frame_offset.1),
+                ],
+                yaw: look.yaw,
+                pitch: look.pitch,
+                timestamp,
+                sneaking: config.ghost_poses && flags.sneaking(),
+                sprinting: config.ghost_poses && flags.sprinting(),
+            };
+
+            let movement = if config.sparse_recording {
+                let Some(movement) = state.hold_redundant_frame(movement) else {
+                    continue;
+                };
+                movement
+            } else {
+                movement
+            };
+
+            state.movements.push(movement);
+            
+            // Limit movements to prevent unbounded memory growth
+            // 30 minutes of gameplay at 20 TPS = 36000 movements
+            const MAX_MOVEMENTS: usize = 36000;
+            if state.movements.len() > MAX_MOVEMENTS {
+                // Remove oldest movements
+                let excess = state.movements.len() - MAX_MOVEMENTS;
+                state.movements.drain(0..excess);
+
+            }
+        }
+    }
+}
+
+/// Index of the frame a replay `elapsed` ms in is on, searching forward from `index`. Frames
+/// are timestamp driven, so any spacing between them works, including big gaps that skip
+/// several frames in one tick or several frames sharing a timestamp.
+fn replay_frame_index(movements: &[PlayerMovement], mut index: usize, elapsed: u128) -> usize {
+    while index + 1 < movements.len() && movements[index + 1].timestamp <= elapsed {
+        index += 1;
+    }
+    index
+}
+
+/// Whether `frame` is close enough to `previous` that a replay looks the same without it.
+fn is_redundant_frame(previous: &PlayerMovement, frame: &PlayerMovement) -> bool {
+    let moved = previous
+        .position
+        .iter()
+        .zip(frame.position)
+        .any(|(previous, current)| (current - previous).abs() > FRAME_POSITION_EPSILON);
+    let turned = (frame.yaw - previous.yaw).abs() > FRAME_ROTATION_EPSILON
+        || (frame.pitch - previous.pitch).abs() > FRAME_ROTATION_EPSILON;
+
+    let posed = frame.sneaking != previous.sneaking || frame.sprinting != previous.sprinting;
+
+    !moved && !turned && !posed
+}
+
+fn update_replay_npcs(
+    mut npcs: Query<(
+        Entity,
+        &mut Position,
+        &mut Look,
+        &mut HeadYaw,
+        &mut Flags,
+        &mut Pose,
+        &mut ReplayNpc,
+        Has<LoopingReplay>,
+    )>,
+    mut clients: Query<(&GameState, &mut Client, Has<Afk>)>,
+    paused: Res<Paused>,
+    config: Res<Config>,
+    mut commands: Commands,
+) {
+    if paused.started_at.is_some() {
+        return;
+    }
+
+    // Since we only have one NPC at a time, we can use single() or iter().next()
+    for (entity, mut pos, mut look, mut head_yaw, mut flags, mut pose, mut replay, looping) in
+        &mut npcs
+    {
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        // Garbage collect ghosts whose owner is gone or that have waited for them for too long
+        let Ok((owner_state, mut owner_client, owner_afk)) = clients.get_mut(replay.owner_entity)
+        else {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        };
+        // Ghosts wait for their owner, who catches their timers up when they're back
+        if owner_afk {
+            continue;
+        }
+
+        // Check if the owner player has started playing (score >= 1)
+        if owner_state.score > 0 && !replay.replay_started {
+            // Player just started, begin the replay
+            replay.replay_started = true;
+            replay.start_time = current_time;
+        }
+
+        // If replay hasn't started yet, keep NPC at first position
+        if !replay.replay_started {
+            let waited = current_time.saturating_sub(replay.spawned_at);
+            if !looping && config.ghost_max_wait_ms.is_some_and(|wait| waited > wait) {
+                commands.entity(entity).insert(Despawned);
+            }
+            continue;
+        }
+        // Check if movements vector is empty
+        if replay.movements.is_empty() {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        }
+
+        let mut elapsed = current_time.saturating_sub(replay.start_time);
+
+        // Looping replays start over from the first frame once they've played through
+        if looping && elapsed > replay.movements[replay.movements.len() - 1].timestamp {
+            replay.current_index = 0;
+            replay.start_time = current_time;
+            elapsed = 0;
+        }
+
+        // Find the appropriate movement frame
+        replay.current_index = replay_frame_index(&replay.movements, replay.current_index, elapsed);
+
+        if elapsed > replay.movements[replay.movements.len() - 1].timestamp {
+            // Replay finished, despawn the NPC
+            commands.entity(entity).insert(Despawned);
+            continue;
+        }
+
+        let previous_pos = pos.0;
 
         // Interpolate between movements for smooth playback
         let current_movement = &replay.movements[replay.current_index];
 
-        if replay.current_index < replay.movements.len() - 1 {
-            let next_movement = &replay.movements[replay.current_index + 1];
-            let time_diff = next_movement.timestamp - current_movement.timestamp;
-            let time_since_current = elapsed.saturating_sub(current_movement.timestamp);
-            let t = if time_diff > 0 {
-                (time_since_current as f64) / (time_diff as f64)
+        // Only touched on changes, every change is sent to the owner
+        if flags.sneaking() != current_movement.sneaking {
+            flags.set_sneaking(current_movement.sneaking);
+            pose.0 = if current_movement.sneaking {
+                EntityPose::Sneaking
+            } else {
+                EntityPose::Standing
+            };
+        }
+        if flags.sprinting() != current_movement.sprinting {
+            flags.set_sprinting(current_movement.sprinting);
+        }
+
+        if replay.current_index < replay.movements.len() - 1 {
+            let next_movement = &replay.movements[replay.current_index + 1];
+            let time_diff = next_movement
+                .timestamp
+                .saturating_sub(current_movement.timestamp);
+            let time_since_current = elapsed.saturating_sub(current_movement.timestamp);
+            let t = if time_diff > 0 {
+                (time_since_current as f64) / (time_diff as f64)
+            } else {
+                1.0
+            };
+            let t = t.clamp(0.0, 1.0);
+
+            // Interpolate position
+            let position = match config.replay_smoothing {
+                ReplaySmoothing::Linear => {
+                    let (from, to) = (current_movement.position, next_movement.position);
+                    [0, 1, 2].map(|axis| from[axis] + (to[axis] - from[axis]) * t)
+                }
+                ReplaySmoothing::CatmullRom => {
+                    // The frames around the segment, repeating the first and last at the ends
+                    let before = &replay.movements[replay.current_index.saturating_sub(1)];
+                    let after = &replay.movements
+                        [(replay.current_index + 2).min(replay.movements.len() - 1)];
+                    [0, 1, 2].map(|axis| {
+                        catmull_rom(
+                            before.position[axis],
+                            current_movement.position[axis],
+                            next_movement.position[axis],
+                            after.position[axis],
+                            t,
+                        )
+                    })
+                }
+            };
+            pos.set(position);
+
+            // Interpolate rotation
+            look.yaw = current_movement.yaw + (next_movement.yaw - current_movement.yaw) * t as f32;
+            look.pitch =
+                current_movement.pitch + (next_movement.pitch - current_movement.pitch) * t as f32;
+            head_yaw.0 = look.yaw;
+        } else {
+            // Use the last movement
+            pos.0.x = current_movement.position[0];
+            pos.0.y = current_movement.position[1];
+            pos.0.z = current_movement.position[2];
+            look.yaw = current_movement.yaw;
+            look.pitch = current_movement.pitch;
+            head_yaw.0 = look.yaw;
+        }
+
+        // Play footsteps from the ghost itself so the owner can hear where it is
+        replay.distance_since_step_sound += pos.0.distance(previous_pos);
+        if replay.distance_since_step_sound >= GHOST_STEP_SOUND_DISTANCE {
+            replay.distance_since_step_sound = 0.0;
+            owner_client.play_sound(
+                Sound::BlockNoteBlockHat,
+                config.sound_category,
+                pos.0,
+                0.6,
+                1.2,
+            );
+        }
+    }
+}
+
+/// Point at `t` between `p1` and `p2` on a uniform Catmull-Rom spline through all four points.
+fn catmull_rom(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+fn show_seeds(
+    mut clients: Query<(
+        Entity,
+        &GameState,
+        &Username,
+        &mut DisplayName,
+        Option<&ShownSeed>,
+    )>,
+    config: Res<Config>,
+    mut commands: Commands,
+) {
+    if !config.show_seeds {
+        return;
+    }
+
+    for (entity, state, username, mut display_name, shown_seed) in &mut clients {
+        if shown_seed.is_some_and(|shown_seed| shown_seed.0 == state.seed) {
+            continue;
+        }
+
+        display_name.0 = Some(
+            username.0.clone().color(Color::WHITE)
+                + format!(" [{}]", seed_code(state.seed)).color(Color::DARK_GRAY),
+        );
+        commands.entity(entity).insert(ShownSeed(state.seed));
+    }
+}
+
+/// `n` with commas between every three digits, like 12,345,678.
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// Short code identifying a seed. Seeds are timestamps, so they're mixed first to keep codes of
+/// seeds a few seconds apart from looking alike.
+fn seed_code(seed: u64) -> String {
+    format!("{:06x}", seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 40)
+}
+
+fn publish_champion_status(
+    globals: Res<Globals>,
+    score_tracker: Res<ScoreTracker>,
+    champion_status: Res<ChampionStatus>,
+    jump_counter: Res<JumpCounter>,
+) {
+    if score_tracker.is_changed() {
+        jump_counter
+            .0
+            .store(score_tracker.total_jumps, Ordering::Relaxed);
+    }
+
+    if !globals.is_changed() {
+        return;
+    }
+
+    *champion_status.0.lock().unwrap() = globals
+        .highscore
+        .as_ref()
+        .map(|highscore| (highscore.username.clone(), highscore.score));
+}
+
+fn run_tutorial(
+    mut clients: Query<(Entity, &mut Client, &GameState, &UniqueId, &mut Tutorial)>,
+    globals: Res<Globals>,
+    mut score_tracker: ResMut<ScoreTracker>,
+    mut commands: Commands,
+) {
+    for (entity, mut client, state, uuid, mut tutorial) in &mut clients {
+        let Some((score, message)) = TUTORIAL_STEPS.get(tutorial.step) else {
+            continue;
+        };
+        if state.score < *score {
+            continue;
+        }
+
+        client.send_chat_message("[Tutorial] ".color(Color::YELLOW) + message.color(Color::WHITE));
+        tutorial.step += 1;
+
+        if tutorial.step == TUTORIAL_STEPS.len() {
+            commands.entity(entity).remove::<Tutorial>();
+            score_tracker.tutorial_done.insert(uuid.0.as_u128());
+            if let Err(e) = save_game_data(&globals.highscore, &score_tracker) {
+                eprintln!("Failed to save game data: {}", e);
+            }
+        }
+    }
+}
+
+fn setup_no_collision_team(
+    new_team_members: Query<(Entity, &Username), Added<NoCollisionTeam>>,
+    team_members: Query<&Username, With<NoCollisionTeam>>,
+    mut all_clients: Query<(Entity, &mut Client)>,
+    teams: Res<Teams>,
+) {
+    if new_team_members.is_empty() {
+        return;
+    }
+
+    // New clients get the whole team, themselves and everyone already in it included.
+    // Rank teams are sent right after and take their members back out
+    let roster: Vec<&str> = team_members
+        .iter()
+        .map(|username| username.0.as_str())
+        .collect();
+    let team_packet = teams.create_packet("no_collision", roster);
+
+    // Everyone else only needs who joined this tick, in one packet
+    let add_packet = TeamS2c {
+        team_name: "no_collision",
+        mode: Mode::AddEntities {
+            entities: new_team_members
+                .iter()
+                .map(|(_, username)| username.0.as_str())
+                .collect(),
+        },
+    };
+
+    for (entity, mut client) in &mut all_clients {
+        if new_team_members.contains(entity) {
+            client.write_packet(&team_packet);
+        } else {
+            client.write_packet(&add_packet);
+        }
+    }
+}
+
+/// How a team is set up on clients.
+struct TeamDefinition {
+    name: &'static str,
+    display_name: &'static str,
+    prefix: Text,
+    color: TeamColor,
+    collision_rule: CollisionRule,
+}
+
+/// Every team players can be put in. Players start out in `no_collision` and move between teams
+/// from there.
+#[derive(Resource)]
+struct Teams {
+    teams: Vec<TeamDefinition>,
+}
+
+impl Teams {
+    fn from_env() -> Self {
+        // Applies to every player team, ranked players shouldn't collide any differently
+        let collision_rule = match config_var("TEAM_COLLISION").as_deref() {
+            Err(_) | Ok("never") => CollisionRule::Never,
+            Ok("always") => CollisionRule::Always,
+            Ok("push_other_teams") => CollisionRule::PushOtherTeams,
+            Ok("push_own_team") => CollisionRule::PushOwnTeam,
+            Ok(other) => panic!(
+                "Unknown TEAM_COLLISION {:?}, expected never, always, push_other_teams or \
+                 push_own_team",
+                other
+            ),
+        };
+
+        let mut teams = Teams { teams: Vec::new() };
+        teams.add(
+            "no_collision",
+            "No Collision",
+            Text::default(),
+            TeamColor::White,
+            collision_rule,
+        );
+        for (name, display_name, prefix, color) in rank_teams() {
+            teams.add(name, display_name, prefix, color, collision_rule);
+        }
+        teams.add(
+            AFK_TEAM,
+            "AFK",
+            "[AFK] ".color(Color::GRAY),
+            TeamColor::White,
+            collision_rule,
+        );
+        // Ghosts can't collide anyway, being spectators
+        teams.add(
+            GHOST_TEAM,
+            "Ghosts",
+            Text::default(),
+            TeamColor::Gray,
+            CollisionRule::Never,
+        );
+        teams
+    }
+
+    fn add(
+        &mut self,
+        name: &'static str,
+        display_name: &'static str,
+        prefix: Text,
+        color: TeamColor,
+        collision_rule: CollisionRule,
+    ) {
+        self.teams.push(TeamDefinition {
+            name,
+            display_name,
+            prefix,
+            color,
+            collision_rule,
+        });
+    }
+
+    /// Packet creating the team called `name` on a client, with `entities` already in it.
+    fn create_packet<'a>(&'a self, name: &'a str, entities: Vec<&'a str>) -> TeamS2c<'a> {
+        let team = self
+            .teams
+            .iter()
+            .find(|team| team.name == name)
+            .unwrap_or_else(|| panic!("Unknown team {}", name));
+
+        TeamS2c {
+            team_name: team.name,
+            mode: Mode::CreateTeam {
+                team_display_name: team.display_name.into_text().into(),
+                friendly_flags: TeamFlags::default(),
+                name_tag_visibility: NameTagVisibility::Always,
+                collision_rule: team.collision_rule,
+                team_color: team.color,
+                team_prefix: team.prefix.clone().into(),
+                team_suffix: Text::default().into(),
+                entities,
+            },
+        }
+    }
+}
+
+/// What stepping on a special block does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SpecialBlockAction {
+    /// Race the champion's ghost on a copy of their course.
+    RaceChampion,
+    /// Spectate the champion's run.
+    WatchChampion,
+}
+
+struct SpecialBlock {
+    pos: BlockPos,
+    state: BlockState,
+    action: SpecialBlockAction,
+}
+
+/// Blocks placed next to every player's start block that do something when stepped on.
+#[derive(Resource)]
+struct SpecialBlocks {
+    blocks: Vec<SpecialBlock>,
+}
+
+impl SpecialBlocks {
+    fn from_env() -> Self {
+        let blocks = vec![
+            SpecialBlock {
+                pos: special_block_pos("GOLD_BLOCK_OFFSET", [2, 0, 0]),
+                state: BlockState::GOLD_BLOCK,
+                action: SpecialBlockAction::RaceChampion,
+            },
+            SpecialBlock {
+                pos: special_block_pos("WATCH_BLOCK_OFFSET", [-2, 0, 0]),
+                state: BlockState::DIAMOND_BLOCK,
+                action: SpecialBlockAction::WatchChampion,
+            },
+        ];
+        for (i, block) in blocks.iter().enumerate() {
+            assert!(
+                blocks[..i].iter().all(|other| other.pos != block.pos),
+                "Special blocks must not share a position"
+            );
+        }
+        SpecialBlocks { blocks }
+    }
+
+    fn place(&self, layer: &mut ChunkLayer) {
+        for block in &self.blocks {
+            layer.set_block(block.pos, block.state);
+        }
+    }
+
+    /// Action of the special block at `pos`, as long as it hasn't been replaced.
+    fn action_at(&self, pos: BlockPos, layer: &ChunkLayer) -> Option<SpecialBlockAction> {
+        self.blocks
+            .iter()
+            .find(|block| {
+                block.pos == pos && layer.block(pos).unwrap_or_default().state == block.state
+            })
+            .map(|block| block.action)
+    }
+}
+
+/// Reads a special block's offset from the start block as `x,y,z`. It has to be at or behind the
+/// start row so generated parkour never lands on it.
+fn special_block_pos(var: &str, default: [i32; 3]) -> BlockPos {
+    let [x, y, z] = match config_var(var) {
+        Ok(offset) => offset
+            .split(',')
+            .map(|part| {
+                part.trim()
+                    .parse::<i32>()
+                    .unwrap_or_else(|_| panic!("Failed to parse {}", var))
+            })
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap_or_else(|_| panic!("{} must be formatted as x,y,z", var)),
+        Err(_) => default,
+    };
+    assert!(
+        z <= 0 && [x, y, z] != [0, 0, 0],
+        "{} must be at or behind the start block without replacing it",
+        var
+    );
+    BlockPos::new(START_POS.x + x, START_POS.y + y, START_POS.z + z)
+}
+
+/// Team tagging players who are AFK, which takes priority over their rank.
+const AFK_TEAM: &str = "afk";
+/// Team every ghost is in. Its color grays out their name tag and glow, so players racing right
+/// next to a ghost on the same blocks can always tell which one they control.
+const GHOST_TEAM: &str = "ghosts";
+
+/// Teams giving the best players a name tag prefix and color, as (team name, display name,
+/// prefix, color). The champion gets the first one and the next two players on the score board
+/// get the others. Clients style sidebar entries by team too, so this is also what makes the
+/// podium stand out on the board. Players go back to `no_collision` once they drop out.
+fn rank_teams() -> [(&'static str, &'static str, Text, TeamColor); 3] {
+    // Team colors are limited to the chat colors, red is the closest to bronze
+    [
+        (
+            "rank_champion",
+            "Champion",
+            "\u{265B} ".color(Color::GOLD),
+            TeamColor::Gold,
+        ),
+        (
+            "rank_2",
+            "Second",
+            "#2 ".color(Color::rgb(192, 192, 192)),
+            TeamColor::Gray,
+        ),
+        (
+            "rank_3",
+            "Third",
+            "#3 ".color(Color::rgb(205, 127, 50)),
+            TeamColor::Red,
+        ),
+    ]
+}
+
+/// Puts newly spawned ghosts in the ghost team for the player they're racing, the only one who
+/// can see them.
+fn add_ghosts_to_team(
+    new_entries: Query<(&Username, &GhostPlayerListEntry), Added<GhostPlayerListEntry>>,
+    ghosts: Query<&ReplayNpc>,
+    mut clients: Query<&mut Client>,
+) {
+    for (ghost_name, entry) in &new_entries {
+        let Ok(ghost) = ghosts.get(entry.ghost_entity) else {
+            continue;
+        };
+        let Ok(mut client) = clients.get_mut(ghost.owner_entity) else {
+            continue;
+        };
+
+        client.write_packet(&TeamS2c {
+            team_name: GHOST_TEAM,
+            mode: Mode::AddEntities {
+                entities: vec![ghost_name.0.as_str()],
+            },
+        });
+    }
+}
+
+fn update_rank_teams(
+    new_team_members: Query<(Entity, &Username), Added<NoCollisionTeam>>,
+    mut all_clients: Query<&mut Client>,
+    globals: Res<Globals>,
+    score_tracker: Res<ScoreTracker>,
+    afk_players: Query<&Username, With<Afk>>,
+    new_afk_players: Query<(), Added<Afk>>,
+    mut returned_afk_players: RemovedComponents<Afk>,
+    teams: Res<Teams>,
+    mut ranked: Local<HashMap<String, &'static str>>,
+) {
+    let afk_changed = !new_afk_players.is_empty() | (returned_afk_players.read().count() > 0);
+    if new_team_members.is_empty()
+        && !globals.is_changed()
+        && !score_tracker.is_changed()
+        && !afk_changed
+    {
+        return;
+    }
+
+    // Joining puts players in `no_collision`, so they have to be ranked again
+    for (_, username) in &new_team_members {
+        ranked.remove(&username.0);
+    }
+
+    // New clients need the rank teams along with everyone already in them
+    for (entity, _) in &new_team_members {
+        let Ok(mut client) = all_clients.get_mut(entity) else {
+            continue;
+        };
+
+        let team_names = rank_teams().map(|(team_name, _, _, _)| team_name);
+        for team_name in team_names.into_iter().chain([AFK_TEAM]) {
+            let members = ranked
+                .iter()
+                .filter(|(_, team)| **team == team_name)
+                .map(|(username, _)| username.as_str())
+                .collect();
+            client.write_packet(&teams.create_packet(team_name, members));
+        }
+        // Ghosts are only seen by their owner, who adds them once they're spawned
+        client.write_packet(&teams.create_packet(GHOST_TEAM, vec![]));
+    }
+
+    let [champion_team, second_team, third_team] =
+        rank_teams().map(|(team_name, _, _, _)| team_name);
+    let champion = globals
+        .highscore
+        .as_ref()
+        .map(|highscore| &highscore.username);
+
+    let mut ranks: HashMap<String, &'static str> = HashMap::new();
+    if let Some(champion) = champion {
+        ranks.insert(champion.clone(), champion_team);
+    }
+    let runners_up = score_tracker
+        .top_15(Board::Score)
+        .into_iter()
+        .map(|(username, _)| username)
+        .filter(|username| Some(username) != champion)
+        .zip([second_team, third_team]);
+    ranks.extend(runners_up);
+    for username in &afk_players {
+        ranks.insert(username.0.clone(), AFK_TEAM);
+    }
+
+    // Offline players stay ranked, their entries on the board are styled by team as well
+
+    // Adding a player to a team takes them out of the one they were in
+    let mut moves: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (username, team) in &ranks {
+        if ranked.get(username) != Some(team) {
+            moves.entry(*team).or_default().push(username.as_str());
+        }
+    }
+    for username in ranked.keys() {
+        if !ranks.contains_key(username) {
+            moves
+                .entry("no_collision")
+                .or_default()
+                .push(username.as_str());
+        }
+    }
+
+    for (team_name, entities) in moves {
+        let packet = TeamS2c {
+            team_name,
+            mode: Mode::AddEntities { entities },
+        };
+        for mut client in &mut all_clients {
+            client.write_packet(&packet);
+        }
+    }
+
+    *ranked = ranks;
+}
+
+fn handle_disconnected_clients(
+    mut disconnected_clients: RemovedComponents<Client>,
+    mut query: Query<(
+        &mut GameState,
+        &Username,
+        Option<&ReplayMode>,
+        Option<&Watching>,
+        Option<&Pacer>,
+        Has<Sprint>,
+        Has<Blind>,
+        Option<&SelfGhost>,
+    )>,
+    mut objectives: Query<&mut ObjectiveScores, With<Objective>>,
+    mut globals: ResMut<Globals>,
+    mut score_tracker: ResMut<ScoreTracker>,
+    mut commands: Commands,
+) {
+    for entity in disconnected_clients.read() {
+        if let Ok((
+            mut state,
+            username,
+            replay_mode,
+            watching,
+            pacer,
+            sprinting,
+            blind,
+            self_ghost,
+        )) = query.get_mut(entity)
+        {
+            // A sprint left unfinished is a DNF, it doesn't count as a scored run
+            if sprinting {
+                state.score = 0;
+            }
+
+            if state.score > 0 {
+                count_run(&username.0, &mut score_tracker, &mut objectives, &globals);
+                score_tracker.run_stats.record(state.score, None);
+            }
+
+            // Check if this is a new global highscore
+            let is_new_highscore = !blind && beats_highscore(&globals.highscore, state.score);
+
+            if is_new_highscore {
+                claim_pioneer(&globals, &mut score_tracker, username);
+
+                let highscore = HighScore {
+                    username: username.to_string(),
+                    score: state.score,
+                    seed: state.seed,
+                    movements: state.take_recorded_movements(),
+                    course: state.course.clone(),
+                    reached: state.reached,
+                };
+
+                globals.highscore = Some(highscore);
+
+                println!(
+                    "Player {} disconnected with new highscore: {}",
+                    username, state.score
+                );
+            }
+
+            if state.score > 0 {
+                // Save the run count along with any new highscore
+                if let Err(e) = save_game_data(&globals.highscore, &score_tracker) {
+                    eprintln!("Failed to save game data: {}", e);
+                }
+            }
+
+            // Despawn the NPC belonging to this player when they disconnect
+            if let Some(replay) = replay_mode {
+                if let Some(npc_entity) = replay.spawned_npc {
+                    despawn_ghost(&mut commands, npc_entity);
+                }
+            }
+
+            if let Some(pacer) = pacer {
+                despawn_ghost(&mut commands, pacer.ghost);
+            }
+            if let Some(ghost) = self_ghost.and_then(|self_ghost| self_ghost.ghost) {
+                despawn_ghost(&mut commands, ghost);
+            }
+
+            // Drop the champion course of a player who left while watching it
+            if let Some(watching) = watching {
+                commands.entity(watching.course_layer).insert(Despawned);
+            }
+        }
+    }
+}
+
+/// Whether a run scoring `score` takes the title from `highscore`, a tie keeps the champion.
+/// Runs are checked against the champion as it is when they end, so when several end in the same
+/// tick the best of them is champion and saved last, whichever order they're handled in.
+fn beats_highscore(highscore: &Option<HighScore>, score: u32) -> bool {
+    highscore
+        .as_ref()
+        .map_or(score > 0, |highscore| score > highscore.score)
+}
+
+/// Makes `username` the pioneer if they're setting the server's very first record, which has to
+/// be checked before their highscore is stored. Returns whether they did.
+fn claim_pioneer(globals: &Globals, score_tracker: &mut ScoreTracker, username: &Username) -> bool {
+    if globals.highscore.is_some() || score_tracker.pioneer.is_some() {
+        return false;
+    }
+
+    score_tracker.pioneer = Some(username.to_string());
+    println!("{} set the first record and became the pioneer", username);
+    true
+}
+
+/// Tells a player who reached the champion's last block how they did against the champion.
+fn finish_race(
+    client: &mut Client,
+    state: &GameState,
+    highscore: &HighScore,
+    replay_mode: &ReplayMode,
+) {
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let player_time = current_time.saturating_sub(state.movement_start_time);
+
+    // Both runs are timed from their first landing
+    let finish_block = state.course[replay_mode.finish_index];
+    let champion_time = highscore
+        .movements
+        .iter()
+        .find(|movement| block_under_player(&Position::new(movement.position)) == finish_block)
+        .map(|movement| movement.timestamp);
+
+    client.set_title_times(5, 40, 10);
+    client.set_title("FINISH!".color(Color::GOLD).bold());
+    let result = match champion_time {
+        Some(champion_time) if player_time < champion_time => format!(
+            "You beat {} by {:.1}s!",
+            highscore.username,
+            (champion_time - player_time) as f64 / 1000.0
+        )
+        .color(Color::GREEN),
+        Some(champion_time) => format!(
+            "{} was {:.1}s faster.",
+            highscore.username,
+            (player_time - champion_time) as f64 / 1000.0
+        )
+        .color(Color::YELLOW),
+        None => {
+            format!("You made it to the end of {}'s run!", highscore.username).color(Color::GREEN)
+        }
+    };
+    client.send_chat_message(
+        format!(
+            "You reached the finish in {:.1}s. ",
+            player_time as f64 / 1000.0
+        )
+        .color(Color::WHITE)
+            + result,
+    );
+}
+
+/// Ends a sprint on its last block and puts the time on that length's leaderboard if it's the
+/// player's best. Timed from the first landing, like races.
+fn finish_sprint(
+    client: &mut Client,
+    state: &GameState,
+    sprint: &mut Sprint,
+    username: &Username,
+    score_tracker: &mut ScoreTracker,
+    globals: &Globals,
+) {
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let time = current_time.saturating_sub(state.movement_start_time);
+    sprint.finished = true;
+
+    client.set_title_times(5, 40, 10);
+    client.set_subtitle(format!("{:.2}s", time as f64 / 1000.0).color(Color::WHITE));
+    client.set_title("FINISH!".color(Color::GOLD).bold());
+
+    let best = score_tracker
+        .sprint_times
+        .get(&sprint.blocks)
+        .and_then(|times| times.get(&username.0))
+        .copied();
+    let result = if best.is_none_or(|best| time < best) {
+        score_tracker
+            .sprint_times
+            .entry(sprint.blocks)
+            .or_default()
+            .insert(username.to_string(), time);
+        if let Err(e) = save_game_data(&globals.highscore, score_tracker) {
+            eprintln!("Failed to save game data: {}", e);
+        }
+        "New personal best!".color(Color::GREEN)
+    } else {
+        format!(
+            "Your best is {:.2}s.",
+            best.unwrap_or_default() as f64 / 1000.0
+        )
+        .color(Color::GRAY)
+    };
+    client.send_chat_message(
+        format!(
+            "Sprint to {} blocks finished in {:.2}s. ",
+            sprint.blocks,
+            time as f64 / 1000.0
+        )
+        .color(Color::WHITE)
+            + result,
+    );
+}
+
+/// Counts a finished run towards the player's runs-played board.
+fn count_run(
+    name: &str,
+    score_tracker: &mut ScoreTracker,
+    objectives: &mut Query<&mut ObjectiveScores, With<Objective>>,
+    globals: &Globals,
+) {
+    let runs = score_tracker.runs.get(name).copied().unwrap_or(0) + 1;
+    score_tracker.set(Board::Runs, name, runs);
+
+    show_top_15(Board::Runs, score_tracker, objectives, globals);
+}
+
+/// Shows only the top 15 of `board`. Everyone else stays in `ScoreTracker`, but keeping them on
+/// the objective would grow the sidebar packet with every new player.
+fn show_top_15(
+    board: Board,
+    score_tracker: &ScoreTracker,
+    objectives: &mut Query<&mut ObjectiveScores, With<Objective>>,
+    globals: &Globals,
+) {
+    let Ok(mut objective) = objectives.get_mut(globals.boards[&board].objective) else {
+        return;
+    };
+
+    // The objective only ever holds a top 15, so it's up to date if all of them are on it
+    let top = score_tracker.top_15(board);
+    if top
+        .iter()
+        .all(|(name, score)| objective.get(name) == Some(score))
+    {
+        return;
+    }
+    *objective = ObjectiveScores::with_map(top.into_iter().collect::<HashMap<_, _>>());
+}
+
+/// Shows the top scores in everyone's tab list footer, for players who hide the sidebar.
+fn update_tab_list_leaderboard(
+    score_tracker: Res<ScoreTracker>,
+    mut player_list: ResMut<PlayerList>,
+    mut last_update: Local<u128>,
+    mut shown: Local<Option<Vec<(String, i32)>>>,
+) {
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    if current_time.saturating_sub(*last_update) < TAB_LIST_UPDATE_MS {
+        return;
+    }
+    *last_update = current_time;
+
+    let mut top = score_tracker.top_15(Board::Score);
+    top.truncate(TAB_LIST_TOP);
+    if shown.as_ref() == Some(&top) {
+        return;
+    }
+
+    let mut footer = "\nTop Scores".color(Color::GOLD).bold();
+    if top.is_empty() {
+        footer = footer + "\nNobody has scored yet".color(Color::GRAY).not_bold();
+    }
+    for (rank, (name, score)) in top.iter().enumerate() {
+        footer = footer
+            + (format!("\n{}. ", rank + 1).color(Color::GRAY).not_bold()
+                + name.clone().color(Color::WHITE)
+                + format!(" {}", score).color(Color::YELLOW));
+    }
+    player_list.set_footer(footer);
+    *shown = Some(top);
+}
+
+fn handle_pacer_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, &GameState, Option<&Pacer>)>,
+    globals: Res<Globals>,
+    config: Res<Config>,
+    uuids: Query<&UniqueId>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let mut args = event.command.split_whitespace();
+        if args.next() != Some("pacer") {
+            continue;
+        }
+
+        let Ok((mut client, state, pacer)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        match (args.next(), pacer) {
+            (Some("on"), Some(_)) => {
+                client.send_chat_message("Your pacer is already running.".color(Color::RED));
+            }
+            (Some("on"), None) => {
+                let highscore = match &globals.highscore {
+                    Some(highscore) if !highscore.movements.is_empty() => highscore,
+                    _ => {
+                        client.send_chat_message(
+                            "There is no champion run to pace against yet!".color(Color::RED),
+                        );
+                        continue;
+                    }
+                };
+
+                let style = state.ghost_style.unwrap_or(config.ghost_style);
+                let ghost = spawn_champion_ghost(
+                    &mut commands,
+                    &uuids,
+                    event.executor,
+                    highscore,
+                    style,
+                    true,
+                );
+                commands.entity(ghost).insert(LoopingReplay);
+                commands.entity(event.executor).insert(Pacer { ghost });
+
+                client.send_chat_message(
+                    format!("{}'s ghost will now pace you.", highscore.username)
+                        .color(Color::GREEN),
+                );
+                client.send_chat_message(
+                    "It replays their run on their course, not yours, so it's only a visual pacer."
+                        .italic()
+                        .color(Color::GRAY),
+                );
+            }
+            (Some("off"), Some(pacer)) => {
+                despawn_ghost(&mut commands, pacer.ghost);
+                commands.entity(event.executor).remove::<Pacer>();
+                client.send_chat_message("Pacer turned off.".color(Color::GREEN));
+            }
+            (Some("off"), None) => {
+                client.send_chat_message("You don't have a pacer running.".color(Color::RED));
+            }
+            _ => {
+                client.send_chat_message("Usage: /pacer <on|off>".color(Color::RED));
+            }
+        }
+    }
+}
+
+/// `/selfghost` toggles racing your own last run. Each run then starts on the course of the one
+/// before, with its ghost, so a player keeps retrying the same course until they turn it off.
+fn handle_self_ghost_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, Option<&SelfGhost>)>,
+    config: Res<Config>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("selfghost") {
+            continue;
+        }
+
+        let Ok((mut client, self_ghost)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        match self_ghost {
+            Some(self_ghost) => {
+                if let Some(ghost) = self_ghost.ghost {
+                    despawn_ghost(&mut commands, ghost);
+                }
+                commands.entity(event.executor).remove::<SelfGhost>();
+                client.send_chat_message(
+                    "You're off your own course, new runs get new courses again."
+                        .color(Color::GREEN),
+                );
+            }
+            None if config.hardcore => {
+                client.send_chat_message(
+                    "Racing your own last run is disabled in hardcore mode.".color(Color::RED),
+                );
+            }
+            None => {
+                commands
+                    .entity(event.executor)
+                    .insert(SelfGhost { ghost: None });
+                client.send_chat_message(
+                    "From your next run on, you'll race the ghost of your previous run on the \
+                     same course."
+                        .color(Color::GREEN),
+                );
+            }
+        }
+    }
+}
+
+fn handle_replay_download_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<&mut Client>,
+    globals: Res<Globals>,
+    profile: Res<JumpProfile>,
+    config: Res<Config>,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("replaydownload") {
+            continue;
+        }
+
+        let Ok(mut client) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        let Some(export_dir) = &config.replay_export_dir else {
+            client.send_chat_message(
+                "Replay downloads aren't enabled on this server.".color(Color::RED),
+            );
+            continue;
+        };
+
+        let highscore = match &globals.highscore {
+            Some(highscore) if !highscore.movements.is_empty() => highscore,
+            _ => {
+                client.send_chat_message(
+                    "There is no champion run to download yet!".color(Color::RED),
+                );
+                continue;
+            }
+        };
+
+        // The seed identifies the course, and a new champion run always comes with a new seed
+        let token = format!("{:016x}", highscore.seed);
+        match export_replay(
+            &export_dir.join(format!("{}.json", token)),
+            highscore,
+            &profile,
+        ) {
+            Ok(()) => {
+                client.send_chat_message(
+                    format!(
+                        "{}'s record run is ready for the replay viewer.",
+                        highscore.username
+                    )
+                    .color(Color::GREEN),
+                );
+                client.send_chat_message(format!("Replay code: {}", token).color(Color::AQUA));
+            }
+            Err(e) => {
+                eprintln!("Failed to export replay: {}", e);
+                client.send_chat_message(
+                    "Failed to export the replay, please try again later.".color(Color::RED),
+                );
+            }
+        }
+    }
+}
+
+/// Writes a run to `path` as JSON.
+fn export_replay(
+    path: &Path,
+    highscore: &HighScore,
+    profile: &JumpProfile,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let export = ReplayExport {
+        version: 1,
+        username: highscore.username.clone(),
+        score: highscore.score,
+        seed: highscore.seed,
+        blocks: champion_course(highscore, profile)
+            .iter()
+            .map(|block| [block.x, block.y, block.z])
+            .collect(),
+        frames: highscore
+            .movements
+            .iter()
+            .map(|movement| ReplayExportFrame {
+                pos: movement.position,
+                yaw: movement.yaw,
+                pitch: movement.pitch,
+                timestamp: movement.timestamp as u64,
+            })
+            .collect(),
+        hash: format!("{:016x}", run_hash(highscore)),
+    };
+
+    fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+    fs::write(path, serde_json::to_vec(&export)?)?;
+    Ok(())
+}
+
+fn handle_ghost_style_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, &mut GameState)>,
+    config: Res<Config>,
+) {
+    for event in events.read() {
+        let mut args = event.command.split_whitespace();
+        if args.next() != Some("ghoststyle") {
+            continue;
+        }
+
+        let Ok((mut client, mut state)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        let style = match args.next() {
+            Some("default") => None,
+            Some(name) => match GhostStyle::from_name(name) {
+                Some(style) => Some(style),
+                None => {
+                    client.send_chat_message(
+                        "Usage: /ghoststyle <glow|body|both|default>".color(Color::RED),
+                    );
+                    continue;
+                }
+            },
+            None => {
+                let style = state.ghost_style.unwrap_or(config.ghost_style);
+                client.send_chat_message(
+                    format!(
+                        "Ghosts are shown as {}. Usage: /ghoststyle <glow|body|both|default>",
+                        style.name()
+                    )
+                    .color(Color::GRAY),
+                );
+                continue;
+            }
+        };
+
+        state.ghost_style = style;
+        client.send_chat_message(
+            format!(
+                "Ghosts will be shown as {} from the next one on.",
+                style.unwrap_or(config.ghost_style).name()
+            )
+            .color(Color::GREEN),
+        );
+    }
+}
+
+fn handle_reload_config_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, &Username, &UniqueId)>,
+    mut config: ResMut<Config>,
+    mut profile: ResMut<JumpProfile>,
+    mut permissions: ResMut<Permissions>,
+    states: Query<&GameState>,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("reloadconfig") {
+            continue;
+        }
+
+        let Ok((mut client, username, uuid)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        if !permissions.allows(&config, username, uuid, "reloadconfig") {
+            client.send_chat_message("You don't have permission to do that.".color(Color::RED));
+            continue;
+        }
+
+        // New blocks use the new settings right away, runs in progress keep their course
+        let longest_course = states
+            .iter()
+            .map(|state| state.blocks.len())
+            .max()
+            .unwrap_or(0);
+        match reload_config(longest_course) {
+            Ok((new_config, new_profile, new_permissions)) => {
+                *config = new_config;
+                *profile = new_profile;
+                *permissions = new_permissions;
+                println!("{} reloaded the config", username);
+                client.send_chat_message(
+                    "Reloaded the config, jump profile and permissions.".color(Color::GREEN),
+                );
+            }
+            Err(e) => {
+                client.send_chat_message(format!("Config not reloaded: {}", e).color(Color::RED));
+            }
+        }
+    }
+}
+
+fn handle_race_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, &mut GameState)>,
+    config: Res<Config>,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("race") {
+            continue;
+        }
+
+        let Ok((mut client, mut state)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        if config.hardcore {
+            client.send_chat_message(
+                "Racing the champion is disabled in hardcore mode.".color(Color::RED),
+            );
+            continue;
+        }
+        if !config.race_confirm || state.race_confirmed {
+            client.send_chat_message(
+                "Step on the gold block to race the champion.".color(Color::GRAY),
+            );
+            continue;
+        }
+
+        state.race_confirmed = true;
+        client.send_chat_message(
+            "Race confirmed! Step on the gold block to start.".color(Color::GREEN),
+        );
+    }
+}
+
+fn handle_top_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<&mut Client>,
+    score_tracker: Res<ScoreTracker>,
+) {
+    const DAY_MS: u128 = 24 * 60 * 60 * 1000;
+
+    for event in events.read() {
+        let mut args = event.command.split_whitespace();
+        if args.next() != Some("top") {
+            continue;
+        }
+
+        let Ok(mut client) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let (title, since) = match args.next() {
+            // Days start at midnight UTC
+            Some("today") => (
+                "Top Scores Today",
+                Some(current_time - current_time % DAY_MS),
+            ),
+            Some("week") => ("Top Scores This Week", Some(current_time - 7 * DAY_MS)),
+            Some("all") | None => ("Top Scores", None),
+            Some(_) => {
+                client.send_chat_message("Usage: /top [today|week|all]".color(Color::RED));
+                continue;
+            }
+        };
+
+        let top = score_tracker.top_15_since(Board::Score, since);
+        client.send_chat_message(title.color(Color::GOLD).bold());
+        if top.is_empty() {
+            client.send_chat_message("Nobody has set a best score then.".color(Color::GRAY));
+        }
+        for (rank, (name, score)) in top.iter().enumerate() {
+            client.send_chat_message(
+                format!("{}. ", rank + 1).color(Color::GRAY)
+                    + name.clone().color(Color::WHITE)
+                    + format!(" {}", score).color(Color::YELLOW),
+            );
+        }
+    }
+}
+
+/// `/sprint <blocks>` turns a fresh run into a sprint to that many blocks, and
+/// `/sprint top <blocks>` shows the fastest times for that length.
+fn handle_sprint_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(
+        &mut Client,
+        &GameState,
+        Has<ReplayMode>,
+        Has<Sprint>,
+        Has<Watching>,
+    )>,
+    score_tracker: Res<ScoreTracker>,
+    config: Res<Config>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let mut args = event.command.split_whitespace();
+        if args.next() != Some("sprint") {
+            continue;
+        }
+
+        let Ok((mut client, state, racing, sprinting, watching)) = clients.get_mut(event.executor)
+        else {
+            continue;
+        };
+
+        if config.sprint_lengths.is_empty() {
+            client.send_chat_message("Sprints are disabled.".color(Color::RED));
+            continue;
+        }
+
+        let lengths: Vec<String> = config
+            .sprint_lengths
+            .iter()
+            .map(|length| length.to_string())
+            .collect();
+        let (top, blocks) = match (args.next(), args.next()) {
+            (Some("top"), Some(blocks)) => (true, Some(blocks)),
+            (Some(blocks), None) => (false, Some(blocks)),
+            _ => (false, None),
+        };
+        let Some(blocks) = blocks
+            .and_then(|blocks| blocks.parse::<u32>().ok())
+            .filter(|blocks| config.sprint_lengths.contains(blocks))
+        else {
+            client.send_chat_message(
+                format!("Usage: /sprint [top] <{}>", lengths.join("|")).color(Color::RED),
+            );
+            continue;
+        };
+
+        if top {
+            let top = score_tracker.sprint_top_15(blocks);
+            client.send_chat_message(
+                format!("Fastest Sprints to {} Blocks", blocks)
+                    .color(Color::GOLD)
+                    .bold(),
+            );
+            if top.is_empty() {
+                client.send_chat_message("Nobody has finished this sprint yet.".color(Color::GRAY));
+            }
+            for (rank, (name, time)) in top.iter().enumerate() {
+                client.send_chat_message(
+                    format!("{}. ", rank + 1).color(Color::GRAY)
+                        + name.clone().color(Color::WHITE)
+                        + format!(" {:.2}s", *time as f64 / 1000.0).color(Color::YELLOW),
+                );
+            }
+            continue;
+        }
+
+        if watching || racing || sprinting {
+            client.send_chat_message("You can't start a sprint right now.".color(Color::RED));
+        } else if state.reached > 0 {
+            client.send_chat_message(
+                "Sprints start on the start block, fall off or finish your run first."
+                    .color(Color::RED),
+            );
+        } else {
+            commands.entity(event.executor).insert(Sprint {
+                blocks,
+                finished: false,
+            });
+            client.send_chat_message(
+                format!(
+                    "Sprint to {} blocks! The clock starts on your first landing.",
+                    blocks
+                )
+                .color(Color::GOLD),
+            );
+        }
+    }
+}
+
+/// `/blind` switches a player between regular courses and blind ones, where only the next block is
+/// placed as they land. Only from the start block, so a run is either blind or not.
+fn handle_blind_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<
+        (
+            &mut Client,
+            &mut GameState,
+            &mut ChunkLayer,
+            Has<Blind>,
+            Has<ReplayMode>,
+        ),
+        Without<Watching>,
+    >,
+    profile: Res<JumpProfile>,
+    config: Res<Config>,
+    special_blocks: Res<SpecialBlocks>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("blind") {
+            continue;
+        }
+
+        let Ok((mut client, mut state, mut layer, blind, racing)) = clients.get_mut(event.executor)
+        else {
+            continue;
+        };
+
+        if racing {
+            client.send_chat_message("You can't switch to blind mode in a race.".color(Color::RED));
+            continue;
+        }
+        if state.reached > 0 {
+            client.send_chat_message(
+                "Blind mode can only be switched on the start block, fall off or finish your run \
+                 first."
+                    .color(Color::RED),
+            );
+            continue;
+        }
+
+        // Place the course again from its seed, so it's the same blocks either way
+        state.target_y = 0;
+        let seed = state.seed;
+        state.rng = StdRng::seed_from_u64(seed);
+        restart_course(
+            &mut state,
+            &mut layer,
+            &profile,
+            &config,
+            &special_blocks,
+            VecDeque::new(),
+            !blind,
+        );
+
+        if blind {
+            commands.entity(event.executor).remove::<Blind>();
+            client.send_chat_message("Blind mode off.".color(Color::GREEN));
+        } else {
+            commands.entity(event.executor).insert(Blind);
+            client.send_chat_message(
+                "Blind mode on! Only the next block appears, each as you land. Your scores go on \
+                 the blind board, see /board blind."
+                    .color(Color::GOLD),
+            );
+        }
+    }
+}
+
+fn handle_stats_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<&mut Client>,
+    score_tracker: Res<ScoreTracker>,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("stats") {
+            continue;
+        }
+
+        let Ok(mut client) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        client.send_chat_message(
+            format!(
+                "{} jumps and counting",
+                thousands(score_tracker.total_jumps)
+            )
+            .color(Color::AQUA),
+        );
+
+        let stats = &score_tracker.run_stats;
+        let total: u32 = stats.score_histogram.iter().sum();
+        if total == 0 {
+            client.send_chat_message("No runs have finished yet.".color(Color::GRAY));
+            continue;
+        }
+
+        client.send_chat_message(
+            format!(
+                "{} runs: half reach {}+, one in ten reaches {}+",
+                total,
+                stats.score_reached_by(0.5),
+                stats.score_reached_by(0.1)
+            )
+            .color(Color::GOLD),
+        );
+
+        let most = stats.score_histogram.iter().copied().max().unwrap_or(1);
+        for (bucket, &runs) in stats.score_histogram.iter().enumerate() {
+            if runs == 0 {
+                continue;
+            }
+            let from = bucket as u32 * SCORE_HISTOGRAM_BUCKET;
+            let label = if bucket == SCORE_HISTOGRAM_BUCKETS - 1 {
+                format!("{}+", from)
+            } else {
+                format!("{}-{}", from, from + SCORE_HISTOGRAM_BUCKET - 1)
+            };
+            let bar = "|".repeat((runs as usize * 20).div_ceil(most as usize));
+            client.send_chat_message(
+                format!("{:>7} ", label).color(Color::GRAY)
+                    + bar.color(Color::AQUA)
+                    + format!(" {}", runs).color(Color::WHITE),
+            );
+        }
+
+        let [up, level, down] = stats.falls_by_jump;
+        client.send_chat_message(
+            format!(
+                "Falls on jumps up: {}, level: {}, down: {}",
+                up, level, down
+            )
+            .color(Color::GRAY),
+        );
+    }
+}
+
+fn handle_whereami_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, &Username, &UniqueId, &Position, &GameState)>,
+    config: Res<Config>,
+    permissions: Res<Permissions>,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("whereami") {
+            continue;
+        }
+
+        let Ok((mut client, username, uuid, pos, state)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        if !config.debug_commands && !permissions.allows(&config, username, uuid, "whereami") {
+            client.send_chat_message("You don't have permission to do that.".color(Color::RED));
+            continue;
+        }
+
+        let pos_under_player = block_under_player(pos);
+        let on_block = match state.blocks.position(pos_under_player) {
+            Some(index) => format!("block {} of the course", index),
+            None => "not on a course block".to_string(),
+        };
+
+        client.send_chat_message(
+            format!("Position: {:.3} {:.3} {:.3}", pos.0.x, pos.0.y, pos.0.z).color(Color::GRAY),
+        );
+        client.send_chat_message(
+            format!(
+                "Standing on: {} {} {} ({})",
+                pos_under_player.x, pos_under_player.y, pos_under_player.z, on_block
+            )
+            .color(Color::GRAY),
+        );
+        client.send_chat_message(
+            format!("Seed: {}, target y: {}", state.seed, state.target_y).color(Color::GRAY),
+        );
+    }
+}
+
+fn handle_preview_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, &Username, &UniqueId)>,
+    profile: Res<JumpProfile>,
+    config: Res<Config>,
+    permissions: Res<Permissions>,
+) {
+    for event in events.read() {
+        let mut args = event.command.split_whitespace();
+        if args.next() != Some("preview") {
+            continue;
+        }
+
+        let Ok((mut client, username, uuid)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        if !config.debug_commands && !permissions.allows(&config, username, uuid, "preview") {
+            client.send_chat_message("You don't have permission to do that.".color(Color::RED));
+            continue;
+        }
+
+        let seed = args.next().and_then(|seed| seed.parse::<u64>().ok());
+        let count = match args.next() {
+            Some(count) => count.parse::<usize>().ok(),
+            None => Some(PREVIEW_BLOCKS),
+        };
+        let (Some(seed), Some(count)) = (seed, count) else {
+            client.send_chat_message(
+                format!(
+                    "Usage: /preview <seed> [blocks, up to {}]",
+                    MAX_PREVIEW_BLOCKS
+                )
+                .color(Color::RED),
+            );
+            continue;
+        };
+        let count = count.clamp(1, MAX_PREVIEW_BLOCKS);
+
+        let course = generate_course(seed, count, &profile);
+        let jumps = course
+            .windows(2)
+            .map(|jump| {
+                format!(
+                    "{:+}/{:+}/{:+}",
+                    jump[1].x - jump[0].x,
+                    jump[1].y - jump[0].y,
+                    jump[1].z - jump[0].z
+                )
+            })
+            .collect::<Vec<_>>();
+
+        client.send_chat_message(
+            format!(
+                "Seed {} [{}], first {} jumps as x/y/z:",
+                seed,
+                seed_code(seed),
+                count
+            )
+            .color(Color::YELLOW),
+        );
+        client.send_chat_message(jumps.join(" ").color(Color::GRAY));
+        client.send_chat_message("Heights: ".color(Color::YELLOW) + height_profile(&course));
+    }
+}
+
+/// One bar per block, from the lowest to the highest block of `course`.
+fn height_profile(course: &[BlockPos]) -> Text {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let lowest = course.iter().map(|block| block.y).min().unwrap_or(0);
+    let highest = course.iter().map(|block| block.y).max().unwrap_or(0);
+    let span = (highest - lowest).max(1) as usize;
+    let bars = course
+        .iter()
+        .map(|block| BARS[(block.y - lowest) as usize * (BARS.len() - 1) / span])
+        .collect::<String>();
+
+    bars.color(Color::WHITE) + format!(" (y {} to {})", lowest, highest).color(Color::GRAY)
+}
+
+fn handle_mod_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(
+        &mut Client,
+        &Username,
+        &UniqueId,
+        &mut GameMode,
+        Option<&mut GameState>,
+        Option<&mut Moderating>,
+        Option<&ReplayMode>,
+        Option<&Pacer>,
+        Has<Watching>,
+    )>,
+    config: Res<Config>,
+    permissions: Res<Permissions>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let command = event.command.split_whitespace().next();
+        if command != Some("mod") && command != Some("play") {
+            continue;
+        }
+
+        let Ok((
+            mut client,
+            username,
+            uuid,
+            mut game_mode,
+            state,
+            moderating,
+            replay_mode,
+            pacer,
+            watching,
+        )) = clients.get_mut(event.executor)
+        else {
+            continue;
+        };
+
+        match (command, state, moderating) {
+            (Some("mod"), Some(mut state), None) => {
+                if !permissions.allows(&config, username, uuid, "mod") {
+                    client.send_chat_message(
+                        "You don't have permission to do that.".color(Color::RED),
+                    );
+                    continue;
+                }
+                if watching {
+                    client.send_chat_message(
+                        "Stop watching the champion's run first.".color(Color::RED),
+                    );
+                    continue;
+                }
+
+                for ghost in replay_mode
+                    .and_then(|replay_mode| replay_mode.spawned_npc)
+                    .into_iter()
+                    .chain(pacer.map(|pacer| pacer.ghost))
+                {
+                    despawn_ghost(&mut commands, ghost);
+                }
+
+                let state = mem::replace(&mut *state, GameState::new(0));
+                commands
+                    .entity(event.executor)
+                    .remove::<(GameState, ReplayMode, Pacer, Afk, Sprint)>()
+                    .insert(Moderating { state });
+                *game_mode = GameMode::Spectator;
+
+                println!("{} started moderating", username);
+                client.send_chat_message(
+                    "You're flying freely and your run was abandoned. Use /play to play again."
+                        .color(Color::GREEN),
+                );
+            }
+            (Some("play"), None, Some(mut moderating)) => {
+                // Putting the state back counts as a new player to `reset_clients`, which starts
+                // a fresh run at the start block
+                let state = mem::replace(&mut moderating.state, GameState::new(0));
+                commands
+                    .entity(event.executor)
+                    .remove::<Moderating>()
+                    .insert(state);
+                *game_mode = GameMode::Adventure;
+
+                println!("{} stopped moderating", username);
+                client.send_chat_message("Back to playing!".color(Color::GREEN));
+            }
+            (Some("mod"), _, _) => {
+                client.send_chat_message("You're already moderating.".color(Color::RED));
+            }
+            _ => {
+                client.send_chat_message("You're already playing.".color(Color::RED));
+            }
+        }
+    }
+}
+
+fn handle_afk_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(
+        &mut Client,
+        &mut Position,
+        &GameState,
+        Has<Afk>,
+        Has<Watching>,
+    )>,
+    afk_players: Query<&Username, With<Afk>>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let mut args = event.command.split_whitespace();
+        if args.next() != Some("afk") {
+            continue;
+        }
+
+        let Ok((mut client, mut pos, state, afk, watching)) = clients.get_mut(event.executor)
+        else {
+            continue;
+        };
+
+        if args.next() == Some("list") {
+            let names: Vec<&str> = afk_players
+                .iter()
+                .map(|username| username.0.as_str())
+                .collect();
+            if names.is_empty() {
+                client.send_chat_message("Nobody is AFK.".color(Color::GRAY));
             } else {
-                1.0
-            };
-            let t = t.clamp(0.0, 1.0);
+                client.send_chat_message(format!("AFK: {}", names.join(", ")).color(Color::GRAY));
+            }
+            continue;
+        }
 
-            // Interpolate position
-            pos.0.x = current_movement.position[0]
-                + (next_movement.position[0] - current_movement.position[0]) * t;
-            pos.0.y = current_movement.position[1]
-                + (next_movement.position[1] - current_movement.position[1]) * t;
-            pos.0.z = current_movement.position[2]
-                + (next_movement.position[2] - current_movement.position[2]) * t;
+        if watching {
+            client.send_chat_message("You can't go AFK while watching a run.".color(Color::RED));
+        } else if afk {
+            client.send_chat_message("Just move to carry on with your run.".color(Color::GRAY));
+        } else {
+            go_afk(&mut commands, event.executor, &mut client, &mut pos, state);
+        }
+    }
+}
 
-            // Interpolate rotation
-            look.yaw = current_movement.yaw + (next_movement.yaw - current_movement.yaw) * t as f32;
-            look.pitch =
-                current_movement.pitch + (next_movement.pitch - current_movement.pitch) * t as f32;
-            head_yaw.0 = look.yaw;
+/// Offers `/skip` to players who seem to be stuck on their current block.
+fn offer_skip(
+    mut clients: Query<(&mut Client, &mut GameState), (Without<Watching>, Without<Afk>)>,
+    config: Res<Config>,
+    paused: Res<Paused>,
+) {
+    let Some(timeout) = config.stuck_timeout_ms else {
+        return;
+    };
+    if paused.started_at.is_some() {
+        return;
+    }
+
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+
+    for (mut client, mut state) in &mut clients {
+        // Waiting on the start block isn't being stuck
+        if state.score == 0
+            || state.skip_offered_for == state.last_block_timestamp
+            || current_time.saturating_sub(state.last_block_timestamp) < timeout
+        {
+            continue;
+        }
+
+        state.skip_offered_for = state.last_block_timestamp;
+        client.send_chat_message(
+            "Stuck? Use /skip to swap the next block for an easier one.".color(Color::YELLOW),
+        );
+    }
+}
+
+/// Nudges players who linger at spawn without starting a run, so the start and the gold block
+/// are for playing. Courses are placed as the run resets, which is when the wait starts.
+fn nudge_idle_at_spawn(
+    mut clients: Query<
+        (&mut Client, &mut GameState),
+        (Without<Watching>, Without<Afk>, Without<Tutorial>),
+    >,
+    config: Res<Config>,
+    paused: Res<Paused>,
+) {
+    let Some(timeout) = config.spawn_idle_timeout_ms else {
+        return;
+    };
+    if paused.started_at.is_some() {
+        return;
+    }
+
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+
+    for (mut client, mut state) in &mut clients {
+        if state.score != 0
+            || state.idle_nudged_for == state.last_block_timestamp
+            || current_time.saturating_sub(state.last_block_timestamp) < timeout
+        {
+            continue;
+        }
+
+        state.idle_nudged_for = state.last_block_timestamp;
+        client.send_chat_message(
+            "Ready? Jump onto the first block to start your run, or step on the gold block to \
+             race the champion."
+                .color(Color::YELLOW),
+        );
+    }
+}
+
+/// Scatters a few flames at `KILL_Y` under each player who has the kill plane on, sent with the
+/// long distance flag since it's further below than particles normally render.
+fn show_kill_plane(
+    mut clients: Query<(&mut Client, &Position, &mut KillPlane), Without<Watching>>,
+) {
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+
+    for (mut client, pos, mut kill_plane) in &mut clients {
+        if current_time.saturating_sub(kill_plane.shown_at) < KILL_PLANE_INTERVAL_MS {
+            continue;
+        }
+        kill_plane.shown_at = current_time;
+
+        client.play_particle(
+            &Particle::Flame,
+            true,
+            [pos.0.x, f64::from(KILL_Y), pos.0.z],
+            [4.0, 0.0, 4.0],
+            0.0,
+            24,
+        );
+    }
+}
+
+/// `/killplane` toggles showing where falling ends the run.
+fn handle_kill_plane_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, Has<KillPlane>)>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("killplane") {
+            continue;
+        }
+
+        let Ok((mut client, shown)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+
+        if shown {
+            commands.entity(event.executor).remove::<KillPlane>();
+            client.send_chat_message("Kill plane hidden.".color(Color::GREEN));
         } else {
-            // Use the last movement
-            pos.0.x = current_movement.position[0];
-            pos.0.y = current_movement.position[1];
-            pos.0.z = current_movement.position[2];
-            look.yaw = current_movement.yaw;
-            look.pitch = current_movement.pitch;
-            head_yaw.0 = look.yaw;
+            commands
+                .entity(event.executor)
+                .insert(KillPlane { shown_at: 0 });
+            client.send_chat_message(
+                "The flames below show where your run ends if you fall.".color(Color::GREEN),
+            );
+        }
+    }
+}
+
+fn handle_skip_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(
+        &mut Client,
+        &mut GameState,
+        &mut ChunkLayer,
+        Has<Watching>,
+        Has<Afk>,
+        Has<Sprint>,
+    )>,
+    profile: Res<JumpProfile>,
+    config: Res<Config>,
+    paused: Res<Paused>,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("skip") {
+            continue;
+        }
+
+        let Ok((mut client, mut state, mut layer, watching, afk, sprinting)) =
+            clients.get_mut(event.executor)
+        else {
+            continue;
+        };
+
+        if config.hardcore {
+            client.send_chat_message(
+                "Skipping blocks is disabled in hardcore mode.".color(Color::RED),
+            );
+            continue;
+        }
+        if sprinting {
+            client.send_chat_message("Skipping blocks is disabled in sprints.".color(Color::RED));
+            continue;
+        }
+        let Some(timeout) = config.stuck_timeout_ms else {
+            client.send_chat_message("Skipping blocks is disabled.".color(Color::RED));
+            continue;
+        };
+
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        if paused.started_at.is_some() || watching || afk {
+            client.send_chat_message("You can't skip right now.".color(Color::RED));
+        } else if state.score == 0 {
+            client.send_chat_message("You can only skip during a run.".color(Color::RED));
+        } else if current_time.saturating_sub(state.last_block_timestamp) < timeout {
+            client.send_chat_message(
+                format!(
+                    "You can skip once you've been stuck on a block for {}s.",
+                    timeout / 1000
+                )
+                .color(Color::RED),
+            );
+        } else {
+            skip_next_block(&mut state, &mut layer, &profile, &config);
+            client
+                .send_chat_message("Swapped the next block for an easier one.".color(Color::GREEN));
+        }
+    }
+}
+
+/// Puts a player on their last block and freezes their run until they move again.
+fn go_afk(
+    commands: &mut Commands,
+    entity: Entity,
+    client: &mut Client,
+    pos: &mut Position,
+    state: &GameState,
+) {
+    let block = state.blocks[0];
+    let position = [
+        f64::from(block.x) + 0.5,
+        f64::from(block.y) + 1.0,
+        f64::from(block.z) + 0.5,
+    ];
+    pos.set(position);
+
+    commands.entity(entity).insert(Afk {
+        since: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis(),
+        position,
+    });
+    client.send_chat_message(
+        "You're now AFK, your run is paused. Move to carry on.".color(Color::GRAY),
+    );
+}
+
+fn update_afk(
+    mut clients: Query<
+        (
+            Entity,
+            &mut Client,
+            &mut Position,
+            &mut GameState,
+            Option<&Afk>,
+        ),
+        Without<Watching>,
+    >,
+    mut npcs: Query<&mut ReplayNpc>,
+    config: Res<Config>,
+    paused: Res<Paused>,
+    mut commands: Commands,
+) {
+    // The maintenance pause already freezes everyone
+    if paused.started_at.is_some() {
+        return;
+    }
+
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+
+    for (entity, mut client, mut pos, mut state, afk) in &mut clients {
+        let position = pos.0.to_array();
+
+        if let Some(afk) = afk {
+            let dx = position[0] - afk.position[0];
+            let dz = position[2] - afk.position[2];
+            if dx.hypot(dz) <= AFK_MOVE_THRESHOLD {
+                continue;
+            }
+
+            // Shift the run's timers by the time spent away so it carries on where it left off
+            let away_for = current_time.saturating_sub(afk.since);
+            state.last_block_timestamp += away_for;
+            state.movement_start_time += away_for;
+            for mut replay in &mut npcs {
+                if replay.owner_entity == entity {
+                    replay.start_time += away_for;
+                    replay.spawned_at += away_for;
+                }
+            }
+
+            state.last_active = current_time;
+            state.last_position = position;
+            commands.entity(entity).remove::<Afk>();
+            client.send_chat_message("Welcome back!".color(Color::GREEN));
+            continue;
+        }
+
+        if position != state.last_position {
+            state.last_active = current_time;
+            state.last_position = position;
+        } else if let Some(timeout) = config.afk_timeout_ms {
+            if current_time.saturating_sub(state.last_active) > timeout {
+                go_afk(&mut commands, entity, &mut client, &mut pos, &state);
+            }
+        }
+    }
+}
+
+fn handle_pause_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, &Username, &UniqueId)>,
+    mut states: Query<&mut GameState>,
+    mut npcs: Query<&mut ReplayNpc>,
+    mut afk_players: Query<&mut Afk>,
+    mut paused: ResMut<Paused>,
+    config: Res<Config>,
+    permissions: Res<Permissions>,
+) {
+    for event in events.read() {
+        if event.command.split_whitespace().next() != Some("pause") {
+            continue;
+        }
+
+        let Ok((mut client, username, uuid)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        if !permissions.allows(&config, username, uuid, "pause") {
+            client.send_chat_message("You don't have permission to do that.".color(Color::RED));
+            continue;
+        }
+        let username = username.to_string();
+
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        match paused.started_at.take() {
+            None => {
+                paused.started_at = Some(current_time);
+                println!("{} paused the game", username);
+
+                for (mut client, _, _) in &mut clients {
+                    // Keep the title up until the game is resumed
+                    client.set_title_times(10, i32::MAX, 10);
+                    client.set_subtitle("Your run is safe, hang tight!".color(Color::GRAY));
+                    client.set_title("Paused".color(Color::GOLD).bold());
+                }
+            }
+            Some(started_at) => {
+                // Shift every timer by the time spent paused so combos and replays carry on
+                // exactly where they left off
+                let paused_for = current_time.saturating_sub(started_at);
+                for mut state in &mut states {
+                    state.last_block_timestamp += paused_for;
+                    state.movement_start_time += paused_for;
+                }
+                for mut replay in &mut npcs {
+                    replay.start_time += paused_for;
+                    replay.spawned_at += paused_for;
+                }
+                // Players away through the pause only catch up on the rest of their time away
+                // when they're back, the pause is already accounted for
+                for mut afk in &mut afk_players {
+                    afk.since += paused_for;
+                }
+                println!("{} resumed the game after {}ms", username, paused_for);
+
+                for (mut client, _, _) in &mut clients {
+                    client.reset_title();
+                }
+            }
+        }
+    }
+}
+
+/// `/setchampion <username>` makes an online player's run so far the champion run, whether or not
+/// it beats the current one, for events and corrections.
+fn handle_set_champion_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, &Username, &UniqueId)>,
+    players: Query<(&Username, &GameState)>,
+    mut globals: ResMut<Globals>,
+    mut score_tracker: ResMut<ScoreTracker>,
+    config: Res<Config>,
+    permissions: Res<Permissions>,
+) {
+    for event in events.read() {
+        let mut args = event.command.split_whitespace();
+        if args.next() != Some("setchampion") {
+            continue;
+        }
+
+        let Ok((mut client, username, uuid)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        if !permissions.allows(&config, username, uuid, "setchampion") {
+            client.send_chat_message("You don't have permission to do that.".color(Color::RED));
+            continue;
+        }
+
+        let Some(target) = args.next() else {
+            client.send_chat_message("Usage: /setchampion <username>".color(Color::RED));
+            continue;
+        };
+        let Some((target, state)) = players
+            .iter()
+            .find(|(name, _)| name.0.eq_ignore_ascii_case(target))
+        else {
+            client.send_chat_message(format!("{} isn't online.", target).color(Color::RED));
+            continue;
+        };
+
+        let movements = state.recorded_movements();
+        if state.score == 0 || movements.is_empty() {
+            client.send_chat_message(
+                format!("{} has no run going to make champion.", target).color(Color::RED),
+            );
+            continue;
+        }
+
+        let replaced = globals.highscore.as_ref().map_or_else(
+            || "nobody".to_string(),
+            |highscore| format!("{} ({})", highscore.username, highscore.score),
+        );
+        println!(
+            "{} manually made {} champion with a score of {}, replacing {}",
+            username, target, state.score, replaced
+        );
+
+        claim_pioneer(&globals, &mut score_tracker, target);
+        globals.highscore = Some(HighScore {
+            username: target.to_string(),
+            score: state.score,
+            seed: state.seed,
+            movements,
+            course: state.course.clone(),
+            reached: state.reached,
+        });
+
+        match save_game_data(&globals.highscore, &score_tracker) {
+            Ok(()) => client.send_chat_message(
+                format!(
+                    "{} is now champion with a score of {}.",
+                    target, state.score
+                )
+                .color(Color::GREEN),
+            ),
+            Err(e) => {
+                eprintln!("Failed to save game data: {}", e);
+                client.send_chat_message(
+                    format!(
+                        "{} is now champion, but the run could not be saved and may be lost on \
+                         restart.",
+                        target
+                    )
+                    .color(Color::RED),
+                );
+            }
         }
     }
 }
 
-fn setup_no_collision_team(
-    new_team_members: Query<(&Username, Entity), Added<NoCollisionTeam>>,
-    mut all_clients: Query<&mut Client>,
-    mut team_created: Local<bool>,
-) {
-    let new_members: Vec<String> = new_team_members
-        .iter()
-        .map(|(username, _)| username.0.clone())
-        .collect();
+fn save_game_data(
+    highscore: &Option<HighScore>,
+    score_tracker: &ScoreTracker,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Only save the top 15 of each board
+    let save_data = SaveData {
+        scoreboard: score_tracker.top_15(Board::Score),
+        combo_board: score_tracker.top_15(Board::Combo),
+        runs_board: score_tracker.top_15(Board::Runs),
+        tutorial_done: score_tracker.tutorial_done.iter().copied().collect(),
+        pioneer: score_tracker.pioneer.clone(),
+        run_stats: score_tracker.run_stats.clone(),
+        total_jumps: score_tracker.total_jumps,
+        achieved_at: Board::ALL
+            .into_iter()
+            .map(|board| {
+                score_tracker
+                    .top_15(board)
+                    .into_iter()
+                    .filter_map(|(name, _)| {
+                        let time = *score_tracker.achieved_at.get(&board)?.get(&name)?;
+                        Some((name, time))
+                    })
+                    .collect()
+            })
+            .collect(),
+        sprint_times: score_tracker
+            .sprint_times
+            .keys()
+            .map(|&blocks| (blocks, score_tracker.sprint_top_15(blocks)))
+            .collect(),
+        blind_board: score_tracker.top_15(Board::Blind),
+        ..highscore_save_data(highscore)
+    };
+    write_save_data(&data_path(GAME_DATA_FILE), &save_data)
+}
 
-    if new_members.is_empty() {
-        return;
+/// Save data with only the fields holding `highscore` filled in.
+fn highscore_save_data(highscore: &Option<HighScore>) -> SaveData {
+    SaveData {
+        // The movements are saved as `highscore_frames`
+        highscore: highscore.as_ref().map(|highscore| HighScore {
+            movements: Vec::new(),
+            ..highscore.clone()
+        }),
+        highscore_course: highscore
+            .iter()
+            .flat_map(|highscore| &highscore.course)
+            .map(|block| [block.x, block.y, block.z])
+            .collect(),
+        highscore_hash: highscore.as_ref().map(run_hash),
+        highscore_reached: highscore.as_ref().map(|highscore| highscore.reached as u64),
+        highscore_frames: highscore
+            .as_ref()
+            .map(|highscore| encode_frames(&highscore.movements))
+            .unwrap_or_default(),
+        ..Default::default()
     }
+}
 
-    // Create team if it doesn't exist
-    if !*team_created {
-        let team_packet = TeamS2c {
-            team_name: "no_collision",
-            mode: Mode::CreateTeam {
-                team_display_name: "No Collision".into_text().into(),
-                friendly_flags: TeamFlags::default(),
-                name_tag_visibility: NameTagVisibility::Always,
-                collision_rule: CollisionRule::Never,
-                team_color: TeamColor::White,
-                team_prefix: Text::default().into(),
-                team_suffix: Text::default().into(),
-                entities: vec![],
-            },
-        };
+fn load_game_data() -> Result<SaveData, Box<dyn std::error::Error>> {
+    read_save_data(&data_path(GAME_DATA_FILE))
+}
 
-        for mut client in &mut all_clients {
-            client.write_packet(&team_packet);
-        }
+/// Writes `save_data` to `path` as bincode (legacy config), with the fields of `SaveData` laid
+/// out back to back in declaration order.
+fn write_save_data(path: &Path, save_data: &SaveData) -> Result<(), Box<dyn std::error::Error>> {
+    let data = bincode::serde::encode_to_vec(save_data, bincode::config::legacy())?;
+    fs::write(path, data)?;
+    Ok(())
+}
 
-        *team_created = true;
+/// Reads data written by `write_save_data`. A missing file is an empty save.
+fn read_save_data(path: &Path) -> Result<SaveData, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Ok(SaveData::default());
     }
 
-    // Add new members to the team
-    let add_packet = TeamS2c {
-        team_name: "no_collision",
-        mode: Mode::AddEntities {
-            entities: new_members.iter().map(|s| s.as_str()).collect(),
-        },
+    let data = fs::read(path)?;
+
+    // Bincode encodes a struct as its fields back to back, so decoding them one at a time
+    // lets files written before a field was appended load with that field defaulted.
+    let mut reader = SaveReader {
+        data: &data,
+        offset: 0,
     };
+    let mut save_data = SaveData {
+        highscore: reader.next()?,
+        scoreboard: reader.next()?,
+        combo_board: reader.next()?,
+        runs_board: reader.next()?,
+        tutorial_done: reader.next()?,
+        highscore_course: reader.next()?,
+        pioneer: reader.next()?,
+        highscore_hash: reader.next()?,
+        run_stats: reader.next()?,
+        total_jumps: reader.next()?,
+        achieved_at: reader.next()?,
+        highscore_frames: reader.next()?,
+        sprint_times: reader.next()?,
+        blind_board: reader.next()?,
+        highscore_reached: reader.next()?,
+    };
+
+    if let Some(highscore) = &mut save_data.highscore {
+        highscore.course = save_data
+            .highscore_course
+            .iter()
+            .map(|&[x, y, z]| BlockPos::new(x, y, z))
+            .collect();
+        if !save_data.highscore_frames.is_empty() {
+            highscore.movements = decode_frames(&save_data.highscore_frames)?;
+        }
+        // Older runs all had `BLOCKS_AHEAD` blocks generated past their last landing, and ones
+        // without a stored course are regenerated to match
+        highscore.reached = match save_data.highscore_reached {
+            Some(reached) => reached as usize,
+            None if highscore.course.is_empty() => highscore.score as usize,
+            None => highscore.course.len().saturating_sub(BLOCKS_AHEAD + 1),
+        };
 
-    for mut client in &mut all_clients {
-        client.write_packet(&add_packet);
+        // Saves from before the hash was stored can't be checked
+        if let Some(expected) = save_data.highscore_hash {
+            if run_hash(highscore) != expected {
+                eprintln!(
+                    "Warning: champion run by {} in {} doesn't match its hash, discarding it",
+                    highscore.username,
+                    path.display()
+                );
+                save_data.highscore = None;
+                save_data.highscore_course.clear();
+                save_data.highscore_hash = None;
+            }
+        }
     }
+    Ok(save_data)
 }
 
-fn handle_disconnected_clients(
-    mut disconnected_clients: RemovedComponents<Client>,
-    query: Query<(&GameState, &Username, Option<&ReplayMode>)>,
-    mut globals: ResMut<Globals>,
-    score_tracker: Res<ScoreTracker>,
-    mut commands: Commands,
-) {
-    for entity in disconnected_clients.read() {
-        if let Ok((state, username, replay_mode)) = query.get(entity) {
-            // Check if this is a new global highscore
-            let is_new_highscore = if let Some(ref existing_highscore) = globals.highscore {
-                state.score > existing_highscore.score
-            } else {
-                state.score > 0
-            };
-
-            if is_new_highscore {
-                let highscore = HighScore {
-                    username: username.to_string(),
-                    score: state.score,
-                    seed: state.seed,
-                    movements: state.movements.clone(),
-                };
+/// Hash of everything that makes up a run, for catching corrupted or edited champion runs. Uses
+/// FNV-1a over the bincode encoding, since the hash is persisted and must not change between
+/// builds the way `DefaultHasher` may.
+fn run_hash(highscore: &HighScore) -> u64 {
+    let course: Vec<[i32; 3]> = highscore
+        .course
+        .iter()
+        .map(|block| [block.x, block.y, block.z])
+        .collect();
+    let data = bincode::serde::encode_to_vec(
+        (
+            &highscore.username,
+            highscore.score,
+            highscore.seed,
+            highscore
+                .movements
+                .iter()
+                .map(LegacyFrame::from)
+                .collect::<Vec<_>>(),
+            course,
+        ),
+        bincode::config::legacy(),
+    )
+    .expect("Failed to encode run");
 
-                globals.highscore = Some(highscore);
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
 
-                // Get current top 15 from score tracker
-                let mut current_top_15: Vec<(String, i32)> = score_tracker
-                    .scores
-                    .iter()
-                    .map(|(k, v)| (k.clone(), *v))
-                    .collect();
-                current_top_15.sort_by(|a, b| b.1.cmp(&a.1));
-                current_top_15.truncate(15);
-
-                // Save the highscore along with current scoreboard
-                if let Err(e) = save_game_data(&globals.highscore, &current_top_15) {
-                    eprintln!("Failed to save highscore: {}", e);
-                }
+/// Version of the format written by `encode_frames`. Fields are only ever appended to frames,
+/// which older builds skip and `decode_frames` defaults, so this only changes when that's not
+/// enough.
+const FRAME_FORMAT_VERSION: u32 = 1;
 
-                println!(
-                    "Player {} disconnected with new highscore: {}",
-                    username, state.score
+/// Encodes each frame as its own byte string with the fields back to back, after
+/// `FRAME_FORMAT_VERSION`. Poses are left out of runs recorded without them.
+fn encode_frames(movements: &[PlayerMovement]) -> Vec<u8> {
+    let with_poses = movements
+        .iter()
+        .any(|movement| movement.sneaking || movement.sprinting);
+    let frames: Vec<Vec<u8>> = movements
+        .iter()
+        .map(|movement| {
+            let mut frame = bincode::serde::encode_to_vec(
+                (
+                    movement.position,
+                    movement.yaw,
+                    movement.pitch,
+                    movement.timestamp,
+                ),
+                bincode::config::legacy(),
+            )
+            .expect("Failed to encode frame");
+            if with_poses {
+                frame.extend(
+                    bincode::serde::encode_to_vec(
+                        (movement.sneaking, movement.sprinting),
+                        bincode::config::legacy(),
+                    )
+                    .expect("Failed to encode frame"),
                 );
             }
+            frame
+        })
+        .collect();
+    bincode::serde::encode_to_vec((FRAME_FORMAT_VERSION, frames), bincode::config::legacy())
+        .expect("Failed to encode frames")
+}
 
-            // Despawn the NPC belonging to this player when they disconnect
-            if let Some(replay) = replay_mode {
-                if let Some(npc_entity) = replay.spawned_npc {
-                    commands.entity(npc_entity).insert(Despawned);
-                }
-            }
-        }
+/// Reads frames written by `encode_frames`, defaulting fields appended after they were written.
+fn decode_frames(data: &[u8]) -> Result<Vec<PlayerMovement>, Box<dyn std::error::Error>> {
+    let ((version, frames), _): ((u32, Vec<Vec<u8>>), _) =
+        bincode::serde::decode_from_slice(data, bincode::config::legacy())?;
+    if version > FRAME_FORMAT_VERSION {
+        return Err(format!(
+            "Frames are in format {}, but only up to {} is supported",
+            version, FRAME_FORMAT_VERSION
+        )
+        .into());
     }
-}
 
-fn save_game_data(
-    highscore: &Option<HighScore>,
-    scoreboard: &[(String, i32)],
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Only save top 15 scores
-    let top_15: Vec<(String, i32)> = scoreboard.iter().take(15).cloned().collect();
+    frames
+        .iter()
+        .map(
+            |frame| -> Result<PlayerMovement, Box<dyn std::error::Error>> {
+                let mut reader = SaveReader {
+                    data: frame,
+                    offset: 0,
+                };
+                Ok(PlayerMovement {
+                    position: reader.next()?,
+                    yaw: reader.next()?,
+                    pitch: reader.next()?,
+                    timestamp: reader.next()?,
+                    sneaking: reader.next()?,
+                    sprinting: reader.next()?,
+                })
+            },
+        )
+        .collect()
+}
 
-    let save_data = SaveData {
-        highscore: highscore.clone(),
-        scoreboard: top_15,
-    };
-    let data = bincode::serde::encode_to_vec(&save_data, bincode::config::legacy())?;
-    fs::write("gamedata.dat", data)?;
-    Ok(())
+struct SaveReader<'a> {
+    data: &'a [u8],
+    offset: usize,
 }
 
-fn load_game_data() -> Result<SaveData, Box<dyn std::error::Error>> {
-    let path = Path::new("gamedata.dat");
-    if !path.exists() {
-        return Ok(SaveData {
-            highscore: None,
-            scoreboard: Vec::new(),
-        });
+impl SaveReader<'_> {
+    fn next<T: DeserializeOwned + Default>(&mut self) -> Result<T, bincode::error::DecodeError> {
+        if self.offset >= self.data.len() {
+            return Ok(T::default());
+        }
+
+        let (value, read) = bincode::serde::decode_from_slice(
+            &self.data[self.offset..],
+            bincode::config::legacy(),
+        )?;
+        self.offset += read;
+        Ok(value)
     }
+}
 
-    let data = fs::read(path)?;
-    let save_data = bincode::serde::decode_from_slice(&data, bincode::config::legacy())?;
-    Ok(save_data.0)
+fn handle_board_command(
+    mut events: EventReader<CommandExecutionEvent>,
+    mut clients: Query<(&mut Client, &mut VisibleEntityLayers, &mut SelectedBoard)>,
+    globals: Res<Globals>,
+) {
+    for event in events.read() {
+        let mut args = event.command.split_whitespace();
+        if args.next() != Some("board") {
+            continue;
+        }
+
+        let Ok((mut client, mut visible_entity_layers, mut selected)) =
+            clients.get_mut(event.executor)
+        else {
+            continue;
+        };
+
+        // Without an argument, cycle to the next board
+        let board = match args.next() {
+            Some(name) => match Board::from_name(name) {
+                Some(board) => board,
+                None => {
+                    client.send_chat_message(
+                        format!("Unknown board '{}'. Try score, combo, runs or blind.", name)
+                            .color(Color::RED),
+                    );
+                    continue;
+                }
+            },
+            None => selected.0.next(),
+        };
+
+        visible_entity_layers
+            .0
+            .remove(&globals.boards[&selected.0].layer);
+        visible_entity_layers.0.insert(globals.boards[&board].layer);
+        selected.0 = board;
+
+        client.send_chat_message(format!("Now showing: {}", board.title()).color(Color::GREEN));
+    }
 }
 
 fn cleanup_ghost_player_list_entries(
@@ -1044,3 +6088,640 @@ fn debug_entity_counts(
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parkourqueue::{combo_window_ms, is_reachable, jump_gap, jump_z_range};
+
+    /// A file in the temp dir for `name`, unique to this test run.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("parkourqueue-{}-{}", std::process::id(), name))
+    }
+
+    fn frame(timestamp: u128, position: [f64; 3]) -> PlayerMovement {
+        PlayerMovement {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            timestamp,
+            sneaking: false,
+            sprinting: false,
+        }
+    }
+
+    fn champion() -> HighScore {
+        HighScore {
+            username: "Steve".to_string(),
+            score: 2,
+            seed: 42,
+            movements: vec![
+                frame(0, [0.5, 101.0, 0.5]),
+                PlayerMovement {
+                    yaw: 12.5,
+                    sprinting: true,
+                    ..frame(250, [1.5, 101.4, 2.5])
+                },
+                frame(600, [0.5, 102.0, 5.5]),
+            ],
+            course: vec![
+                START_POS,
+                BlockPos::new(1, 100, 3),
+                BlockPos::new(0, 101, 5),
+                BlockPos::new(-2, 101, 8),
+            ],
+            reached: 2,
+        }
+    }
+
+    #[test]
+    fn save_data_round_trips() {
+        let path = temp_path("round-trip");
+        let highscore = champion();
+        let written = SaveData {
+            scoreboard: vec![("Steve".to_string(), 2), ("Alex".to_string(), 1)],
+            combo_board: vec![("Alex".to_string(), 3)],
+            runs_board: vec![("Steve".to_string(), 5), ("Alex".to_string(), 4)],
+            tutorial_done: vec![7, 9],
+            pioneer: Some("Alex".to_string()),
+            run_stats: RunStats {
+                score_histogram: vec![1, 2],
+                falls_by_jump: [1, 0, 3],
+            },
+            total_jumps: 12,
+            achieved_at: vec![vec![("Steve".to_string(), 1000)], Vec::new()],
+            sprint_times: vec![(25, vec![("Alex".to_string(), 9000)])],
+            blind_board: vec![("Steve".to_string(), 1)],
+            ..highscore_save_data(&Some(highscore.clone()))
+        };
+
+        write_save_data(&path, &written).unwrap();
+        let loaded = read_save_data(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.highscore, Some(highscore));
+        // The written highscore has its movements in `highscore_frames` instead
+        assert_eq!(
+            SaveData {
+                highscore: None,
+                ..loaded
+            },
+            SaveData {
+                highscore: None,
+                ..written
+            }
+        );
+    }
+
+    #[test]
+    fn legacy_save_loads_with_appended_fields_defaulted() {
+        let path = temp_path("legacy");
+        let highscore = HighScore {
+            course: Vec::new(),
+            reached: 0,
+            ..champion()
+        };
+        let scoreboard = vec![("Steve".to_string(), 2)];
+        // The first saves only held these two, with the frames inside the highscore
+        let data = bincode::serde::encode_to_vec(
+            (Some(&highscore), &scoreboard),
+            bincode::config::legacy(),
+        )
+        .unwrap();
+        fs::write(&path, data).unwrap();
+        let loaded = read_save_data(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // Legacy frames have no poses, and a run without a course reached its score
+        let movements = highscore
+            .movements
+            .iter()
+            .map(|movement| PlayerMovement {
+                sneaking: false,
+                sprinting: false,
+                ..movement.clone()
+            })
+            .collect();
+        assert_eq!(
+            loaded.highscore,
+            Some(HighScore {
+                movements,
+                reached: highscore.score as usize,
+                ..highscore
+            })
+        );
+        assert_eq!(loaded.scoreboard, scoreboard);
+        assert_eq!(
+            SaveData {
+                highscore: None,
+                scoreboard: Vec::new(),
+                ..loaded
+            },
+            SaveData::default()
+        );
+    }
+
+    #[test]
+    fn combo_continues_within_the_window() {
+        let window = combo_window_ms(5, 100, 1000);
+        assert_eq!(update_combo(0, 1, 999, 100, 1000), 1);
+        assert_eq!(update_combo(5, 1, window - 1, 100, 1000), 6);
+    }
+
+    #[test]
+    fn combo_breaks_outside_the_window() {
+        let window = combo_window_ms(5, 100, 1000);
+        assert_eq!(update_combo(0, 1, 1000, 100, 1000), 0);
+        assert_eq!(update_combo(5, 1, window, 100, 1000), 0);
+        assert_eq!(update_combo(5, 1, u128::MAX, 100, 1000), 0);
+    }
+
+    #[test]
+    fn combo_grows_by_blocks_with_a_window_per_block() {
+        let window = combo_window_ms(5, 100, 1000);
+        assert_eq!(update_combo(5, 3, 3 * window - 1, 100, 1000), 8);
+        assert_eq!(update_combo(5, 3, 3 * window, 100, 1000), 0);
+    }
+
+    #[test]
+    fn instant_landing_keeps_the_combo() {
+        assert_eq!(update_combo(0, 1, 0, 100, 1000), 1);
+        assert_eq!(update_combo(1000, 2, 0, 100, 1000), 1002);
+        assert_eq!(update_combo(u32::MAX - 1, 2, 0, 100, 1000), u32::MAX);
+    }
+
+    #[test]
+    fn combo_window_stays_between_floor_and_ceiling() {
+        assert_eq!(combo_window_ms(0, 100, 1000), 1000);
+        assert_eq!(combo_window_ms(45, 100, 1000), 500);
+        assert_eq!(combo_window_ms(u32::MAX, 100, 1000), 100);
+
+        for combo in [0, 45, u32::MAX] {
+            for (floor, ceiling) in [(1, 1), (100, 1000), (999, 1000), (1, u128::from(u32::MAX))] {
+                let window = combo_window_ms(combo, floor, ceiling);
+                assert!(
+                    (floor..=ceiling).contains(&window),
+                    "window {} for combo {} outside {}..={}",
+                    window,
+                    combo,
+                    floor,
+                    ceiling
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reachability_of_known_jumps() {
+        let from = START_POS;
+        // Offsets from `from`, the air between the blocks and whether it can be jumped
+        let jumps = [
+            // Longest default jumps up, level and down, diagonal as far as x goes
+            ((3, 1, 2), 5_f64.sqrt(), true),
+            ((3, 0, 3), 8_f64.sqrt(), true),
+            ((3, -1, 4), 13_f64.sqrt(), true),
+            // Longest straight gaps, and one block past them
+            ((0, 1, 4), 3.0, true),
+            ((0, 1, 5), 4.0, false),
+            ((0, 0, 5), 4.0, true),
+            ((0, 0, 6), 5.0, false),
+            ((0, -1, 5), 4.0, true),
+            ((0, -1, 6), 5.0, false),
+            // Diagonal gaps count the air on both axes
+            ((3, 1, 3), 8_f64.sqrt(), true),
+            ((4, 1, 3), 13_f64.sqrt(), false),
+            ((4, 0, 4), 18_f64.sqrt(), false),
+            // Blocks next to each other, even diagonally, have no gap
+            ((1, 0, 1), 0.0, true),
+            ((1, 1, 1), 0.0, true),
+            // Nothing is reachable more than a block up
+            ((0, 2, 1), 0.0, false),
+            ((0, 2, 2), 1.0, false),
+        ];
+
+        for ((dx, dy, dz), gap, reachable) in jumps {
+            let to = BlockPos::new(from.x + dx, from.y + dy, from.z + dz);
+            assert!(
+                (jump_gap(from, to) - gap).abs() < 1e-9,
+                "gap to {:?}",
+                (dx, dy, dz)
+            );
+            assert_eq!(
+                is_reachable(from, to),
+                reachable,
+                "jump to {:?}",
+                (dx, dy, dz)
+            );
+            // The gap doesn't depend on the direction
+            assert_eq!(jump_gap(from, to), jump_gap(to, from));
+        }
+    }
+
+    fn frames_at(timestamps: &[u128]) -> Vec<PlayerMovement> {
+        timestamps
+            .iter()
+            .map(|&timestamp| frame(timestamp, [0.0; 3]))
+            .collect()
+    }
+
+    /// The frame a replay of `movements` is on after each 50 ms tick, until its last frame.
+    fn play_back(movements: &[PlayerMovement]) -> Vec<usize> {
+        let end = movements
+            .iter()
+            .map(|movement| movement.timestamp)
+            .max()
+            .unwrap();
+        let mut index = 0;
+        (0..=end.div_ceil(50))
+            .map(|tick| {
+                index = replay_frame_index(movements, index, tick * 50);
+                index
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replay_follows_sparse_frames() {
+        let movements = frames_at(&[0, 50, 2000, 2050]);
+        assert_eq!(replay_frame_index(&movements, 1, 1000), 1);
+        assert_eq!(replay_frame_index(&movements, 1, 1999), 1);
+        assert_eq!(replay_frame_index(&movements, 1, 2000), 2);
+        // A big step skips every frame due in it
+        let movements = frames_at(&[0, 10, 20, 30, 1000]);
+        assert_eq!(replay_frame_index(&movements, 0, 25), 2);
+        assert_eq!(replay_frame_index(&movements, 0, 5000), 4);
+        assert_eq!(replay_frame_index(&frames_at(&[0]), 0, 5000), 0);
+    }
+
+    #[test]
+    fn replay_passes_frames_sharing_a_timestamp() {
+        let movements = frames_at(&[0, 100, 100, 100, 200]);
+        assert_eq!(replay_frame_index(&movements, 0, 99), 0);
+        assert_eq!(replay_frame_index(&movements, 0, 100), 3);
+        assert_eq!(replay_frame_index(&movements, 3, 150), 3);
+        assert_eq!(replay_frame_index(&movements, 3, 200), 4);
+    }
+
+    #[test]
+    fn replay_never_goes_back_or_stalls() {
+        for timestamps in [
+            &[0, 50, 2000, 2050][..],
+            &[0, 100, 100, 100, 200],
+            &[0, 300, 100, 400],
+            &[0, 100, 50, 300],
+            &[0, 7, 13, 499, 500, 501, 1250],
+        ] {
+            let indices = play_back(&frames_at(timestamps));
+            assert!(
+                indices.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{:?} played back as {:?}",
+                timestamps,
+                indices
+            );
+            assert_eq!(indices.last(), Some(&(timestamps.len() - 1)));
+        }
+    }
+
+    #[test]
+    fn top_15_breaks_ties_by_time_then_name() {
+        let mut score_tracker = ScoreTracker::default();
+        for (name, score) in [
+            ("dave", 10),
+            ("carol", 10),
+            ("bob", 10),
+            ("alice", 10),
+            ("erin", 20),
+        ] {
+            score_tracker.scores.insert(name.to_string(), score);
+        }
+        // Alice's and Bob's entries are from before times were kept
+        score_tracker.achieved_at.insert(
+            Board::Score,
+            HashMap::from([
+                ("carol".to_string(), 2000),
+                ("dave".to_string(), 1000),
+                ("erin".to_string(), 3000),
+            ]),
+        );
+
+        let expected: Vec<(String, i32)> = [
+            ("erin", 20),
+            ("dave", 10),
+            ("carol", 10),
+            ("alice", 10),
+            ("bob", 10),
+        ]
+        .into_iter()
+        .map(|(name, score)| (name.to_string(), score))
+        .collect();
+        assert_eq!(score_tracker.top_15(Board::Score), expected);
+
+        // The order doesn't depend on how the entries were inserted
+        let reinserted: HashMap<String, i32> = score_tracker.scores.drain().collect();
+        score_tracker.scores = reinserted;
+        assert_eq!(score_tracker.top_15(Board::Score), expected);
+    }
+
+    #[test]
+    fn top_15_truncates_ties_deterministically() {
+        let mut score_tracker = ScoreTracker::default();
+        for i in 0..20 {
+            score_tracker.scores.insert(format!("player{:02}", i), 5);
+        }
+        let top = score_tracker.top_15(Board::Score);
+        let names: Vec<String> = top.into_iter().map(|(name, _)| name).collect();
+        let expected: Vec<String> = (0..15).map(|i| format!("player{:02}", i)).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn ghost_names_cut_between_multibyte_characters() {
+        assert_eq!(ghost_name("Steve"), "Steve Ghost");
+        // 10 characters but 20 bytes, which fits
+        assert_eq!(ghost_name("ÄÖÜäöüßéèê"), "ÄÖÜäöüßéèê Ghost");
+        assert_eq!(ghost_name("ÄÖÜäöüßéèêÄÖÜ"), "ÄÖÜäöüß. Ghost");
+        assert_eq!(
+            ghost_name("日本語のユーザー名です"),
+            "日本語のユーザ. Ghost"
+        );
+
+        for username in [
+            "ÄÖÜäöüßéèê",
+            "ÄÖÜäöüßéèêÄÖÜ",
+            "日本語のユーザー名です",
+            "abcdefghijklmnop",
+        ] {
+            assert!(ghost_name(username).chars().count() <= 16, "{}", username);
+        }
+    }
+
+    #[test]
+    fn best_run_of_a_tick_becomes_champion() {
+        let run = |username: &str, score| HighScore {
+            username: username.to_string(),
+            score,
+            ..champion()
+        };
+
+        for runs in [
+            [run("alex", 120), run("steve", 90)],
+            [run("steve", 90), run("alex", 120)],
+        ] {
+            let mut highscore = Some(run("notch", 100));
+            for run in runs {
+                if beats_highscore(&highscore, run.score) {
+                    highscore = Some(run);
+                }
+            }
+            let highscore = highscore.unwrap();
+            assert_eq!(
+                (highscore.username.as_str(), highscore.score),
+                ("alex", 120)
+            );
+        }
+    }
+
+    #[test]
+    fn tie_keeps_the_champion() {
+        assert!(!beats_highscore(&Some(champion()), champion().score));
+        assert!(beats_highscore(&Some(champion()), champion().score + 1));
+        // Any score takes an empty title, but not none at all
+        assert!(beats_highscore(&None, 1));
+        assert!(!beats_highscore(&None, 0));
+    }
+
+    #[test]
+    fn sparse_recording_reduces_a_stationary_segment() {
+        let mut frames = Vec::new();
+        // Jumping onto a block
+        for i in 0..5 {
+            frames.push(frame(i * 50, [0.5, 101.0, 0.5 + i as f64 * 0.4]));
+        }
+        // Standing on it for two seconds, with jitter too small to see
+        for i in 0..40 {
+            let jitter = if i % 2 == 0 { 0.0004 } else { -0.0004 };
+            frames.push(PlayerMovement {
+                yaw: jitter as f32 * 100.0,
+                ..frame(250 + i * 50, [0.5 + jitter, 101.0, 2.5 + jitter])
+            });
+        }
+        // And jumping off again
+        for i in 0..5 {
+            frames.push(frame(2250 + i * 50, [0.5, 101.0, 2.9 + i as f64 * 0.4]));
+        }
+
+        let mut state = GameState::new(0);
+        for movement in frames.clone() {
+            if let Some(movement) = state.hold_redundant_frame(movement) {
+                state.movements.push(movement);
+            }
+        }
+        let recorded = state.recorded_movements();
+
+        // The moving frames, the first and last frame of the stop, and nothing in between
+        assert_eq!(recorded.len(), 12);
+        assert_eq!(recorded[..6], frames[..6]);
+        assert_eq!(recorded[6], frames[44]);
+        assert_eq!(recorded[7..], frames[45..]);
+    }
+
+    #[test]
+    fn pose_changes_are_never_redundant() {
+        let standing = frame(0, [0.5, 101.0, 0.5]);
+        assert!(is_redundant_frame(&standing, &frame(50, [0.5, 101.0, 0.5])));
+        assert!(!is_redundant_frame(
+            &standing,
+            &PlayerMovement {
+                sneaking: true,
+                ..frame(50, [0.5, 101.0, 0.5])
+            }
+        ));
+        assert!(!is_redundant_frame(
+            &standing,
+            &frame(50, [0.5, 101.0, 0.6])
+        ));
+    }
+
+    /// `JumpProfile::from_env` with nothing set.
+    fn default_profile() -> JumpProfile {
+        JumpProfile {
+            flow: None,
+            tiers: Vec::new(),
+            y_band: 30,
+            adaptive: false,
+            z_up: (1, 2),
+            z_level: (1, 3),
+            z_down: (2, 4),
+            min_gap: 0.0,
+        }
+    }
+
+    #[test]
+    fn generation_never_lands_on_special_blocks() {
+        let special_blocks = SpecialBlocks::from_env();
+        let is_special = |pos: BlockPos| special_blocks.blocks.iter().any(|block| block.pos == pos);
+        let profile = default_profile();
+
+        // The special blocks are closest to the start block, so jumping off it is where a course
+        // could reach them
+        for seed in 0..100 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            for _ in 0..100 {
+                let block_pos =
+                    generate_random_block(START_POS, 0, &profile, f64::INFINITY, &mut rng);
+                assert!(
+                    !is_special(block_pos),
+                    "seed {} generated {:?}",
+                    seed,
+                    block_pos
+                );
+                assert!(block_pos.z > START_POS.z);
+            }
+        }
+
+        for seed in 0..100 {
+            let course = generate_course(seed, 200, &profile);
+            assert_eq!(course[0], START_POS);
+            assert!(
+                course[1..].iter().all(|&block_pos| !is_special(block_pos)),
+                "seed {} ran over a special block",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn jumps_stay_reachable_in_every_direction() {
+        let tuned = JumpProfile {
+            z_up: (2, 4),
+            z_level: (1, 5),
+            z_down: (3, 5),
+            ..default_profile()
+        };
+
+        for profile in [default_profile(), tuned] {
+            // A target above or below the start forces jumps up or down, and full flow level ones
+            let directions = [
+                (1, START_POS.y + 10, None),
+                (0, 0, Some(1.0)),
+                (-1, START_POS.y - 10, None),
+            ];
+            for (dy, target_y, flow) in directions {
+                let profile = JumpProfile {
+                    flow,
+                    ..profile.clone()
+                };
+                let (min_z, max_z) = profile.z_range(dy);
+                for seed in 0..50 {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    for _ in 0..50 {
+                        let block_pos = generate_random_block(
+                            START_POS,
+                            target_y,
+                            &profile,
+                            f64::INFINITY,
+                            &mut rng,
+                        );
+                        assert_eq!(block_pos.y - START_POS.y, dy);
+                        assert!((min_z..=max_z).contains(&(block_pos.z - START_POS.z)));
+                        assert!(
+                            is_reachable(START_POS, block_pos),
+                            "seed {} generated unreachable {:?}",
+                            seed,
+                            block_pos
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn jump_ranges_must_be_reachable() {
+        // Nothing's set for this, so the default is checked
+        let var = "PARKOURQUEUE_TEST_JUMP_Z";
+        assert_eq!(jump_z_range(var, 1, (1, 4)), Ok((1, 4)));
+        assert!(jump_z_range(var, 1, (1, 5)).is_err());
+        assert_eq!(jump_z_range(var, 0, (1, 5)), Ok((1, 5)));
+        assert!(jump_z_range(var, 0, (1, 6)).is_err());
+        // Falling goes as far as a level jump, but no further
+        assert_eq!(jump_z_range(var, -1, (2, 5)), Ok((2, 5)));
+        assert!(jump_z_range(var, -1, (2, 6)).is_err());
+        assert!(jump_z_range(var, 0, (3, 2)).is_err());
+        assert!(jump_z_range(var, 0, (0, 2)).is_err());
+    }
+
+    /// A player standing in the middle of `block`.
+    fn standing_on(block: BlockPos) -> Position {
+        let mut pos = Position::default();
+        pos.set([
+            f64::from(block.x) + 0.5,
+            f64::from(block.y) + 1.0,
+            f64::from(block.z) + 0.5,
+        ]);
+        pos
+    }
+
+    #[test]
+    fn recording_starts_on_a_first_landing_past_block_1() {
+        let mut state = GameState::new(7);
+        for block_pos in generate_course(7, 10, &default_profile()) {
+            state.blocks.push_back(block_pos);
+        }
+
+        // The first landing skips blocks 1 and 2
+        let index = course_block_under_player(&state, &standing_on(state.blocks[3]));
+        assert_eq!(index, Some(3));
+        assert!(!state.recording_started);
+        state.start_recording(1000);
+        assert!(state.recording_started);
+        assert_eq!(state.movement_start_time, 1000);
+
+        // Later landings keep the frames timed from the first
+        state.blocks.drain_front(3);
+        let index = course_block_under_player(&state, &standing_on(state.blocks[1]));
+        assert_eq!(index, Some(1));
+        state.start_recording(2500);
+        assert_eq!(state.movement_start_time, 1000);
+    }
+
+    fn scoring_config(combo_bonus: f64) -> Config {
+        Config {
+            combo_bonus,
+            combo_window_floor_ms: 100,
+            combo_window_ceiling_ms: 1000,
+            ..Config::from_env().unwrap()
+        }
+    }
+
+    #[test]
+    fn landings_score_a_point_per_block_and_the_combo_bonus() {
+        let config = scoring_config(0.5);
+        // Blocks ahead and time since the previous landing
+        let landings = [(1, 400), (1, 500), (2, 900), (1, 2000), (1, 300)];
+
+        let mut combo = 0;
+        let mut score = 0;
+        let mut combos = Vec::new();
+        for (blocks, elapsed_ms) in landings {
+            let (new_combo, bonus_points) = score_landing(combo, blocks, elapsed_ms, &config);
+            combo = new_combo;
+            score += blocks + bonus_points;
+            combos.push(combo);
+        }
+
+        // The fourth landing took too long and broke the combo
+        assert_eq!(combos, [1, 2, 4, 0, 1]);
+        // 6 blocks, and bonuses of 1 for combo 2 and 2 per block for combo 4
+        assert_eq!(score, 6 + 1 + 4);
+    }
+
+    #[test]
+    fn combo_bonus_is_off_by_default_and_saturates() {
+        assert_eq!(score_landing(40, 3, 0, &scoring_config(0.0)), (43, 0));
+        assert_eq!(
+            score_landing(u32::MAX - 1, 5, 0, &scoring_config(MAX_COMBO_BONUS)),
+            (u32::MAX, u32::MAX)
+        );
+    }
+}